@@ -1,4 +1,3 @@
-use mysql::{prelude::Queryable, Conn, Opts, Row};
 use pipelined_server::{
     http::{
         body::{Application, Body, ContentType, Text},
@@ -11,214 +10,72 @@ use pipelined_server::{
 };
 
 use hex_rgb::Color;
-use petgraph::{adj::NodeIndex, Directed, Direction::Outgoing, Graph};
-use pulldown_cmark::{html, Options, Parser};
-use regex::Regex;
-use serde_json::Value;
+use serde_json::{json, Map, Value};
 
 use std::{
-    collections::{HashMap, VecDeque},
-    env,
-    fs::read,
-    path::PathBuf,
-    sync::mpsc::{self, Sender},
-    thread::{self, JoinHandle},
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, OnceLock},
+    time::SystemTime,
 };
 
-use log::{error, trace};
-#[derive(Clone, Debug)]
-pub enum UtilityCommand {
-    GetFile {
-        file: PathBuf,
-        bytes: bool,
+use log::{error, trace, warn};
+
+use crate::{
+    compression::{self, Encoding},
+    conditional, feed, http_date,
+    markdown::{self, Rendered, Renderer, Stats},
+    model::{self, DevLog, Project, Tag, SELECT_RELATED, SELECT_TAGS},
+    range::{self, Ranges},
+    sanitize::sanitize,
+    setting::{MarkdownSetting, Setting},
+    sitemap,
+    template::{escape::url_decode, Template, Value as TemplateValue},
+    utility::{
+        file::{find_files, hash_content},
+        page_cache::Page,
+        Table, UtilityCommand, UtilityData, UtilitySender,
     },
-    DBQuery {
-        statement: String,
-        param: Vec<String>,
-        map_func: fn(Row) -> String,
-    },
-}
-
-#[derive(Clone, Debug)]
-pub enum UtilityData {
-    Bytes(Vec<u8>),
-    String(Vec<String>),
-}
+};
 
 const SELECT_SKILLS: &str = "SELECT * FROM skills";
-const SELECT_PROJECTS: &str = "select `tag`.`id` AS `id`,`tag`.`colour` AS `colour`,`tag`.`tag_name` AS `tag_name`,`dev_log`.`body` AS `body`,`project_details`.`repo` AS  `repo`,`project_details`.`first_push` AS `first_push`,`project_details`.`last_push` AS `last_push` from (((`tag` join `project_details`) join `dev_log`) join `relate_tags`) where ((`tag`.`tag_type` = 1) and (`tag`.`id` = `project_details`.`proj_tag`) and (`relate_tags`.`tag_1` = `project_details`.`proj_tag`) and (`relate_tags`.`tag_2` = `dev_log`.`tag_id`))";
-const SELECT_TAGS: &str = "SELECT `id`,`colour`,`tag_name`,`tag_type` FROM tag WHERE tag_type!=3";
-const SELECT_RELATED: &str = "SELECT `tag_1`,`tag_2` FROM relate_tags";
-
-const NULL: &str = "null";
-
-type RGB = (f64, f64, f64);
-
-pub type UtilitySender = mpsc::Sender<(UtilityCommand, Sender<Result<UtilityData, ()>>)>;
-
-pub fn generate_utility_thread() -> (UtilitySender, JoinHandle<()>) {
-    // generate channel
-    let (tx, rx): (UtilitySender, _) = mpsc::channel();
-
-    // create thread
-    let thread = thread::spawn(move || {
-        let mut db_request_next: VecDeque<(
-            String,
-            Vec<String>,
-            fn(Row) -> String,
-            Sender<Result<UtilityData, ()>>,
-        )> = VecDeque::new();
-        let mut db_request_pool: Vec<JoinHandle<()>> = Vec::new();
-
-        let mut file_request_next: VecDeque<(PathBuf, bool, Sender<Result<UtilityData, ()>>)> =
-            VecDeque::new();
-        let mut file_request_pool: Vec<JoinHandle<()>> = Vec::new();
-
-        loop {
-            if let Ok((utility_command, sender)) = rx.try_recv() {
-                trace!("Cmd: {utility_command:?}");
-                match utility_command {
-                    UtilityCommand::DBQuery {
-                        statement,
-                        param,
-                        map_func,
-                    } => db_request_next.push_back((statement, param, map_func, sender)),
-                    UtilityCommand::GetFile { file, bytes } => {
-                        file_request_next.push_back((file, bytes, sender))
-                    }
-                }
-            }
-
-            if db_request_pool.len() < 1 && !db_request_next.is_empty() {
-                let (statement, params, map_func, sender) = db_request_next.pop_front().unwrap();
-
-                db_request_pool.push(thread::spawn(move || {
-                    let url = {
-                        let host = env::var("DB_host").unwrap();
-                        let name = env::var("DB_name").unwrap();
-                        let user_name = env::var("DB_username").unwrap();
-                        let password = env::var("DB_password").unwrap();
-                        let port = env::var("DB_port").unwrap();
 
-                        format!("mysql://{user_name}:{password}@{host}:{port}/{name}")
-                    };
-
-                    let url = match Opts::from_url(&url) {
-                        Ok(url) => url,
-                        Err(err) => {
-                            error!("Failed to get url: {err}");
-                            let _ = sender.send(Err(()));
-                            return;
-                        }
-                    };
-
-                    let mut conn = match Conn::new(url) {
-                        Ok(url) => url,
-                        Err(err) => {
-                            error!("Failed to conn: {err}");
-                            let _ = sender.send(Err(()));
-                            return;
-                        }
-                    };
-
-                    let statement = match conn.prep(statement) {
-                        Ok(statement) => statement,
-                        Err(err) => {
-                            error!("Failed to prep: {err}");
-                            let _ = sender.send(Err(()));
-                            return;
-                        }
-                    };
-
-                    let rows = match conn.exec_iter(statement, params) {
-                        Ok(val) => val,
-                        Err(err) => {
-                            error!("Failed to execute: {err:#?}");
-                            let _ = sender.send(Err(()));
-                            return;
-                        }
-                    };
-
-                    let mut results = Vec::new();
-
-                    for row in rows {
-                        match row {
-                            Ok(row) => results.push(map_func(row)),
-                            Err(err) => {
-                                error!("Failed to parse data: {err:#?}");
-                                let _ = sender.send(Err(()));
-                                return;
-                            }
-                        };
-                    }
-
-                    let _ = sender.send(Ok(UtilityData::String(results)));
+/// Schema version of the `get_data` response
+const GET_DATA_VERSION: u32 = 1;
 
-                    // let _ = match conn.exec_map(statement, params, map_func) {
-                    //     Ok(val) => sender.send(Ok(UtilityData::String(val))),
-                    //     Err(err) => {
-                    //         error!("Failed to execute: {err:#?}");
-                    //         sender.send(Err(()))
-                    //     },
-                    // };
-                }));
-            }
-
-            if file_request_pool.len() < 1 && !file_request_next.is_empty() {
-                let (path, byte_cond, sender) = file_request_next.pop_front().unwrap();
+const NULL: &str = "null";
 
-                file_request_pool.push(thread::spawn(move || {
-                    let path = path.as_path();
+/// Environment variable holding the token admin requests must present
+pub const ADMIN_TOKEN: &str = "ADMIN_token";
 
-                    //check if file exists
-                    if !path.exists() || !path.is_file() {
-                        let _ = sender.send(Err(()));
-                        return;
-                    }
+type RGB = (f64, f64, f64);
 
-                    let Some(ext) = path.extension().unwrap().to_str() else {
-                        let _ = sender.send(Err(()));
-                        return;
-                    };
+/// Border colour of tags whose colour can't be parsed
+const DEFAULT_TAG_COLOUR: RGB = (128f64, 128f64, 128f64);
 
-                    match (ext, byte_cond) {
-                        ("txt", false) | ("html", false) | ("css", false) | ("template", false) => {
-                            let _ = sender.send(Ok(UtilityData::String({
-                                let tmp = String::from_utf8(read(path).unwrap()).unwrap();
-                                vec![tmp]
-                            })));
-                        }
-                        (_, true) => {
-                            let _ = sender.send(Ok(UtilityData::Bytes(read(path).unwrap())));
-                        }
-                        _ => {
-                            let _ = sender.send(Err(()));
-                        }
-                    }
-                }));
-            }
+pub fn post(
+    request: &Request,
+    setting: &ServerSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let Request(_method, heading) = request;
 
-            //filter our completed threads
-            db_request_pool = db_request_pool
-                .into_iter()
-                .filter(|t| !t.is_finished())
-                .collect();
-            file_request_pool = file_request_pool
-                .into_iter()
-                .filter(|t| !t.is_finished())
-                .collect();
-        }
-    });
+    let response = post_content(request, setting, utility_thread)?;
 
-    (tx, thread)
+    Ok(compression::compress(
+        heading,
+        response,
+        &Setting::get().compression,
+    ))
 }
 
-pub fn post(
+fn post_content(
     request: &Request,
     _setting: &ServerSetting,
     utility_thread: &UtilitySender,
 ) -> Result<Response, ResponseStatusCode> {
-    let Request(method, _heading) = request;
+    let Request(method, heading) = request;
 
     let Method::Post { file, body } = method else {
         panic!();
@@ -246,77 +103,67 @@ pub fn post(
                 return Err(ResponseStatusCode::BadRequest);
             }
 
-            let mut data = requested_data
-                .iter()
-                .filter(|table_name| table_name.is_string())
-                .filter_map(|table_name| table_name.as_str())
-                .filter_map(|table_name| {
-                    let (tx, rx) = mpsc::channel();
-                    let _ = match table_name {
-                        "skills" => utility_thread.send((
-                            UtilityCommand::DBQuery {
-                                statement: String::from(SELECT_SKILLS),
-                                param: Vec::new(),
-                                map_func: parse_db_skills,
-                            },
-                            tx,
-                        )),
-                        "projects" => utility_thread.send((
-                            UtilityCommand::DBQuery {
-                                statement: String::from(SELECT_PROJECTS),
-                                param: Vec::new(),
-                                map_func: parse_db_projects,
-                            },
-                            tx,
-                        )),
-                        "tag" => utility_thread.send((
-                            UtilityCommand::DBQuery {
-                                statement: String::from(SELECT_TAGS),
-                                param: Vec::new(),
-                                map_func: parse_db_tags,
-                            },
-                            tx,
-                        )),
-                        "related" => utility_thread.send((
-                            UtilityCommand::DBQuery {
-                                statement: String::from(SELECT_RELATED),
-                                param: Vec::new(),
-                                map_func: parse_db_related,
-                            },
-                            tx,
-                        )),
-                        _ => return None,
-                    };
+            let mut data = Map::new();
 
-                    let data = match rx.recv() {
-                        Ok(Ok(UtilityData::String(data))) => data,
-                        Ok(Ok(UtilityData::Bytes(_))) => return Some(Err(())),
-                        Ok(Err(_)) => {
-                            error!("Failed utility thread");
-                            return Some(Err(()));
-                        }
-                        Err(err) => {
-                            error!("Failed Channel: {err}");
-                            return Some(Err(()));
-                        }
+            for table_name in requested_data.iter().filter_map(|table_name| table_name.as_str()) {
+                if table_name == "projects" {
+                    let markdown_setting = match heading.get("host") {
+                        Some(host) => Setting::get().markdown(host),
+                        None => MarkdownSetting::default(),
                     };
 
-                    return Some(Ok(format!(
-                        "{{\"name\":\"{}\",\"data\":[{}]}}",
-                        table_name,
-                        data.join(",")
-                    )));
-                });
+                    data.insert(
+                        String::from(table_name),
+                        projects_json(&markdown_setting, utility_thread)?,
+                    );
+                    continue;
+                }
+
+                let (statement, table) = match table_name {
+                    "skills" => (SELECT_SKILLS, Table::Skills),
+                    "tag" => (SELECT_TAGS, Table::Tags),
+                    "related" => (SELECT_RELATED, Table::Relations),
+                    _ => continue,
+                };
+
+                let (tx, rx) = mpsc::channel();
+                let _ = utility_thread.send((
+                    UtilityCommand::DBQuery {
+                        statement: String::from(statement),
+                        param: Vec::new(),
+                        table,
+                    },
+                    tx,
+                ));
+
+                let rows = match rx.recv() {
+                    Ok(Ok(UtilityData::Skills(rows))) => serde_json::to_value(rows),
+                    Ok(Ok(UtilityData::Tags(rows))) => serde_json::to_value(rows),
+                    Ok(Ok(UtilityData::Relations(rows))) => serde_json::to_value(rows),
+                    Ok(Ok(_)) => return Err(ResponseStatusCode::InternalServerError),
+                    Ok(Err(_)) => {
+                        error!("Failed utility thread");
+                        return Err(ResponseStatusCode::InternalServerError);
+                    }
+                    Err(err) => {
+                        error!("Failed Channel: {err}");
+                        return Err(ResponseStatusCode::InternalServerError);
+                    }
+                };
 
-            // if data.any(|data| data.is_err()) {
-            //     return Err(ResponseStatusCode::InternalServerError);
-            // }
+                match rows {
+                    Ok(rows) => data.insert(String::from(table_name), rows),
+                    Err(err) => {
+                        error!("Failed to serialize {table_name}: {err}");
+                        return Err(ResponseStatusCode::InternalServerError);
+                    }
+                };
+            }
 
-            let data = data
-                .into_iter()
-                .filter(|result| result.is_ok())
-                .map(|data| data.unwrap())
-                .collect::<Vec<String>>();
+            let data = json!({
+                "version": GET_DATA_VERSION,
+                "data": data,
+            });
 
             trace!("{data:#?}");
 
@@ -325,113 +172,134 @@ pub fn post(
                 header: HashMap::new(),
                 body: Some(Body {
                     content_type: ContentType::Application(Application::json),
-                    content: format!("{:?}", data).as_bytes().to_vec(),
+                    content: data.to_string().into_bytes(),
                 }),
             });
         }
-        _ => Err(ResponseStatusCode::BadRequest),
-    }
-}
+        "invalidate_pages" => {
+            let Ok(body) = parse_json(body) else {
+                return Err(ResponseStatusCode::BadRequest);
+            };
 
-fn parse_db_skills(row: Row) -> String {
-    let id = match row.get::<i32, usize>(0) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let colour: String = match row.get::<String, usize>(1) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let symbol: String = match row.get::<String, usize>(2) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+            if !is_admin(&body) {
+                return Err(ResponseStatusCode::Forbidden);
+            }
 
-    return format!(
-        "{{\"id\":{}, \"colour\":\"#{}\", \"symbol\":\"{}\"}}",
-        id, colour, symbol
-    );
-}
+            let (tx, rx) = mpsc::channel();
+            let _ = utility_thread.send((UtilityCommand::InvalidatePages, tx));
 
-fn parse_db_projects(row: Row) -> String {
-    let id = match row.get::<i32, usize>(0) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let _colour = match row.get::<String, usize>(1) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let name = match row.get::<String, usize>(2) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let body = match row.get::<String, usize>(3) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let repo = match row.get::<String, usize>(4) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
-    let first_push = match row.get::<mysql::Value, usize>(5) {
-        Some(mysql::Value::Date(year, month, day, _hour, _min, _sec, _ms)) => {
-            format!("{year}-{month}-{day}")
+            let Ok(Ok(_)) = rx.recv() else {
+                return Err(ResponseStatusCode::InternalServerError);
+            };
+
+            Ok(Response {
+                status: ResponseStatusCode::Ok,
+                header: HashMap::new(),
+                body: None,
+            })
         }
-        Some(_) | None => return String::from(""),
-    };
-    let last_push = match row.get::<Option<mysql::Value>, usize>(6) {
-        Some(Some(mysql::Value::Date(year, month, day, _hour, _min, _sec, _ms))) => {
-            format!("{year}-{month}-{day}")
+        "search" => {
+            let Ok(body) = parse_json(body) else {
+                return Err(ResponseStatusCode::BadRequest);
+            };
+
+            let Some(query) = body.get("query").and_then(Value::as_str) else {
+                return Err(ResponseStatusCode::BadRequest);
+            };
+
+            let tags: Vec<String> = match body.get("tags") {
+                Some(Value::Array(tags)) => tags
+                    .iter()
+                    .filter_map(|tag| tag.as_str().map(String::from))
+                    .collect(),
+                Some(Value::String(tag)) => vec![tag.clone()],
+                _ => Vec::new(),
+            };
+
+            let number = |key: &str| {
+                body.get(key)
+                    .and_then(Value::as_u64)
+                    .map(|number| number as usize)
+            };
+
+            search(query, &tags, number("offset"), number("limit"), utility_thread)
         }
-        Some(None) => String::from(NULL),
-        Some(Some(_)) | None => return String::from(""),
-    };
+        "file_cache_stats" => {
+            let Ok(body) = parse_json(body) else {
+                return Err(ResponseStatusCode::BadRequest);
+            };
 
-    return format!(
-        "{{\"Tag\": {id}, \"Start\": \"{first_push}\", \"Update\": \"{last_push}\", \"Title\":\"{name}\", \"Description\": \"{body}\", \"link\":\"{repo}\"}}",
-    );
-}
+            if !is_admin(&body) {
+                return Err(ResponseStatusCode::Forbidden);
+            }
 
-fn parse_db_tags(row: Row) -> String {
-    let id: i32 = match row.get::<i32, usize>(0) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+            let (tx, rx) = mpsc::channel();
+            let _ = utility_thread.send((UtilityCommand::FileCacheStats, tx));
 
-    let colour: String = match row.get::<String, usize>(1) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+            let Ok(Ok(UtilityData::FileCacheStats(stats))) = rx.recv() else {
+                error!("Failed to get file cache stats");
+                return Err(ResponseStatusCode::InternalServerError);
+            };
 
-    let symbol: String = match row.get::<String, usize>(2) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+            Ok(Response {
+                status: ResponseStatusCode::Ok,
+                header: HashMap::new(),
+                body: Some(Body {
+                    content_type: ContentType::Application(Application::json),
+                    content: json!(stats).to_string().into_bytes(),
+                }),
+            })
+        }
+        _ => Err(ResponseStatusCode::BadRequest),
+    }
+}
 
-    let tag_type: i32 = match row.get::<i32, usize>(3) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+/// Project rows of `get_data`, each with the `stats` of its dev log chain
+fn projects_json(
+    markdown_setting: &MarkdownSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Value, ResponseStatusCode> {
+    let projects = load_projects(utility_thread)?
+        .into_iter()
+        .map(|(proj, chain)| {
+            let mut renderer = Renderer::new(markdown_setting);
+            let stats: Stats = chain.iter().map(|dev_log| renderer.skip(&dev_log.body)).sum();
+
+            let mut proj = serde_json::to_value(proj)?;
+            if let Value::Object(proj) = &mut proj {
+                proj.insert(String::from("stats"), stats_json(&stats));
+            }
 
-    return format!(
-        "{{\"id\":{}, \"colour\":\"#{}\", \"symbol\":\"{}\", \"tag_type\":{}}}",
-        id, colour, symbol, tag_type
-    );
+            Ok(proj)
+        })
+        .collect::<Result<Vec<Value>, serde_json::Error>>();
+
+    match projects {
+        Ok(projects) => Ok(Value::Array(projects)),
+        Err(err) => {
+            error!("Failed to serialize projects: {err}");
+            Err(ResponseStatusCode::InternalServerError)
+        }
+    }
 }
 
-fn parse_db_related(row: Row) -> String {
-    let tag_1: i32 = match row.get::<i32, usize>(0) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+fn stats_json(stats: &Stats) -> Value {
+    json!({
+        "words": stats.words,
+        "reading_time": stats.reading_minutes(),
+        "code_blocks": stats.code_blocks,
+        "images": stats.images,
+    })
+}
 
-    let tag_2: i32 = match row.get::<i32, usize>(1) {
-        Some(val) => val,
-        None => return String::from(""),
-    };
+/// Whether the request's `token` matches [`ADMIN_TOKEN`]. Always false while it is unset.
+fn is_admin(body: &Value) -> bool {
+    let admin_token = env::var(ADMIN_TOKEN).ok().filter(|token| !token.is_empty());
 
-    return format!("{{\"tag_1\":{}, \"tag_2\":{}}}", tag_1, tag_2);
+    matches!(
+        (admin_token, body.get("token").and_then(Value::as_str)),
+        (Some(admin_token), Some(token)) if admin_token == token
+    )
 }
 
 fn parse_json(body: &Body) -> Result<serde_json::Value, ParserError> {
@@ -470,6 +338,29 @@ pub fn get(
     request: &Request,
     setting: &ServerSetting,
     utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let Request(_method, heading) = request;
+
+    let compression_setting = &Setting::get().compression;
+
+    let mut response = get_content(request, setting, utility_thread)?;
+
+    let encoding = compression::choose(heading, &response, compression_setting);
+    conditional::add_etag(&mut response, encoding);
+    // a 304 has to vary like the response it stands for
+    compression::add_vary(&mut response);
+
+    if conditional::is_not_modified(heading, &response) {
+        return Ok(conditional::not_modified(response));
+    }
+
+    Ok(compression::compress(heading, response, compression_setting))
+}
+
+fn get_content(
+    request: &Request,
+    setting: &ServerSetting,
+    utility_thread: &UtilitySender,
 ) -> Result<Response, ResponseStatusCode> {
     let Request(method, heading) = request;
 
@@ -477,39 +368,50 @@ pub fn get(
         panic!()
     };
 
-    let Some(host) = heading.get("host") else {
-        return Err(ResponseStatusCode::ImATeapot);
-    };
-
-    let Some((
-        host_path,
-        allowed_extension
-    )) = setting.paths.get(host).map(|host_path| (&host_path.path, &host_path.allow)) else {
-        return Err(ResponseStatusCode::NotFound);
-    };
+    if let Some((format, project_slug)) = feed::route(file) {
+        return get_feed(format, project_slug, heading, setting, utility_thread);
+    }
 
-    trace!("{host_path} := {allowed_extension:#?}");
+    if let Some(part) = sitemap::route(file) {
+        return get_sitemap(part, heading, setting, utility_thread);
+    }
 
-    let file : PathBuf = {
-        let ext: &str;
-        let mut file_path = PathBuf::from(&host_path);
-        let file = file.trim_matches('/').replace("\\","/");
-        file_path.push(&file);
-        
-        if file_path.extension().is_none() {
-            file_path.push("index.html");
-        }
+    if let Some(params) = search_route(file) {
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+        let number = |key: &str| param(key).and_then(|value| value.parse().ok());
+
+        let tags: Vec<String> = params
+            .iter()
+            .filter(|(name, _)| name == "tag")
+            .map(|(_, value)| value.clone())
+            .collect();
+
+        return search(
+            param("q").unwrap_or_default(),
+            &tags,
+            number("offset"),
+            number("limit"),
+            utility_thread,
+        );
+    }
 
-        ext = file_path.extension().unwrap().to_str().unwrap();
+    let (host_path, file) = resolve_file(file, heading, setting)?;
 
-        if !allowed_extension.iter().any(|allowed| allowed == ext) {
-            return Err(ResponseStatusCode::Forbidden);
-        }
+    trace!("file:{:?}", file);
 
-        file_path.to_string_lossy().replace("\\", "/").into()
-    };
+    if let Some(response) = get_range(heading, &file, utility_thread) {
+        return Ok(response);
+    }
 
-    trace!("file:{:?}", file);
+    // serve a precompressed sidecar if the client accepts one
+    if let Some(response) = get_sidecar(heading, &file, utility_thread) {
+        return Ok(response);
+    }
 
     // check file exists
     {
@@ -523,14 +425,36 @@ pub fn get(
             tx,
         ));
 
+        let (tx, metadata_rx) = mpsc::channel();
+        let _ = utility_thread.send((
+            UtilityCommand::GetMetadata {
+                file: file.clone(),
+                digest: true,
+            },
+            tx,
+        ));
+
         if let Ok(Ok(file_content)) = rx.recv() {
             let header = {
                 let mut header = HashMap::new();
 
+                if let Ok(Ok(UtilityData::Metadata {
+                    modified,
+                    hash: Some(hash),
+                    ..
+                })) = metadata_rx.recv()
+                {
+                    header.insert(String::from("ETag"), conditional::etag(&hash));
+
+                    if let Some(modified) = modified {
+                        header.insert(String::from("Last-Modified"), http_date::format(modified));
+                    }
+                }
+
                 header
             };
             let content = match file_content {
-                UtilityData::Bytes(byte) => byte,
+                UtilityData::Bytes(byte) => byte.to_vec(),
                 UtilityData::String(data) => data
                     .into_iter()
                     .map(|content| content.as_bytes().to_vec())
@@ -539,440 +463,1542 @@ pub fn get(
 
                         bytes
                     }),
+                _ => return Err(ResponseStatusCode::InternalServerError),
             };
 
+            let content_type =
+                ContentType::try_from(file.extension().unwrap().to_str().unwrap()).unwrap();
+
+            let mut header = header;
+            if compression::choose_for(
+                heading,
+                &content_type,
+                content.len(),
+                &Setting::get().compression,
+            )
+            .is_none()
+            {
+                header.insert(String::from("Accept-Ranges"), String::from("bytes"));
+            }
+
             return Ok(Response {
                 status: ResponseStatusCode::Ok,
                 header: header,
                 body: Some(Body {
-                    content_type: ContentType::try_from(
-                        file.extension().unwrap().to_str().unwrap(),
-                    )
-                    .unwrap(),
+                    content_type,
                     content,
                 }),
             });
         }
     }
 
-    // get template & db value
-    {
-        let (tx, db_rx) = mpsc::channel();
-        let _ = utility_thread.send((
-            UtilityCommand::DBQuery {
-                statement: format!("select * from ({SELECT_PROJECTS}) as p"),
-                param: vec![],
-                map_func: |row: Row| {
-                    let id = match row.get::<i32, usize>(0) {
-                        Some(val) => val,
-                        None => return String::from(""),
-                    };
-                    let _colour = match row.get::<String, usize>(1) {
-                        Some(val) => val,
-                        None => return String::from(""),
-                    };
-                    let name = match row.get::<String, usize>(2) {
-                        Some(val) => val,
-                        None => return String::from(""),
-                    };
-                    let body = match row.get::<String, usize>(3) {
-                        Some(val) => val,
-                        None => return String::from(""),
-                    };
-                    let repo = match row.get::<String, usize>(4) {
-                        Some(val) => val,
-                        None => return String::from(""),
-                    };
-                    let first_push = match row.get::<mysql::Value, usize>(5) {
-                        Some(mysql::Value::Date(year, month, day, _hour, _min, _sec, _ms)) => {
-                            format!("{year}-{month}-{day}")
-                        }
-                        Some(_) | None => return String::from(""),
-                    };
-                    let last_push = match row.get::<Option<mysql::Value>, usize>(6) {
-                        Some(Some(mysql::Value::Date(
-                            year,
-                            month,
-                            day,
-                            _hour,
-                            _min,
-                            _sec,
-                            _ms,
-                        ))) => format!("{year}-{month}-{day}"),
-                        Some(None) => String::from(NULL),
-                        Some(Some(_)) | None => return String::from(""),
-                    };
+    let markdown_setting = match heading.get("host") {
+        Some(host) => Setting::get().markdown(host),
+        None => MarkdownSetting::default(),
+    };
 
-                    return format!("{id}, {first_push}, {last_push}, {name}, {body}, {repo}",);
-                },
-            },
-            tx,
-        ));
+    get_project_page(host_path, &file, &markdown_setting, utility_thread)
+}
 
-        match db_rx.recv() {
-            Ok(Ok(UtilityData::String(data))) => {
-                let proj = data
-                    .iter()
-                    .map(|proj_raw| {
-                        let proj_raw = proj_raw
-                            .split(",")
-                            .map(|str| String::from(str.trim()))
-                            .collect::<Vec<String>>();
-
-                        // "{id}, {first_push}, {last_push}, {name}, {body}, {repo}",
-                        (
-                            proj_raw[0].clone(),
-                            proj_raw[1].clone(),
-                            proj_raw[2].clone(),
-                            proj_raw[3].clone(),
-                            proj_raw[4].clone(),
-                            proj_raw[5].clone(),
-                        )
-                    })
-                    .filter(|proj| {
-                        // trace!(
-                        //     "\"{}/{}/index.html\"",
-                        //     host_path.replace("\\", "/"),
-                        //     proj.3.replace(" ", "_")
-                        // );
-                        // trace!("{file:?}");
-                        format!(
-                            "\"{}/{}/index.html\"",
-                            host_path.replace("\\", "/"),
-                            proj.3.replace(" ", "_")
-                        ) == format!("{file:?}")
-                    })
-                    .next();
-
-                if let Some(proj) = proj {
-                    //query data async
-                    //get template files
-                    let (tx, dev_log_article_template_rx) = mpsc::channel();
-                    let _ = utility_thread.send((
-                        UtilityCommand::GetFile {
-                            file: {
-                                let mut file_path = PathBuf::from(host_path);
-                                file_path.push("src");
-                                file_path.push("template");
-                                file_path.push("dev_log.article.html.template");
-
-                                file_path.to_string_lossy().replace("\\", "/").into()
-                            },
-                            bytes: false,
-                        },
-                        tx,
-                    ));
-
-                    let (tx, dev_log_template_rx) = mpsc::channel();
-                    let _ = utility_thread.send((
-                        UtilityCommand::GetFile {
-                            file: {
-                                let mut file_path = PathBuf::from(host_path);
-                                file_path.push("src");
-                                file_path.push("template");
-                                file_path.push("dev_log.html.template");
-
-                                file_path.to_string_lossy().replace("\\", "/").into()
-                            },
-                            bytes: false,
-                        },
-                        tx,
-                    ));
-
-                    //query db
-                    let (tx, related_tags_rx) = mpsc::channel();
-                    let _ = utility_thread.send(
-                        (
-                            UtilityCommand::DBQuery {
-                                statement: String::from(
-                                    "SELECT id, colour, tag_name FROM tag WHERE EXISTS(SELECT * FROM relate_tags WHERE relate_tags.tag_1 = ? AND relate_tags.tag_2 = tag.id) AND tag_type != 3"
-                                ),
-                                param: vec![proj.0.clone()],
-                                map_func: |row| {
-                                    //id,colour,tag_name
-                                    let id = row.get::<i32, usize>(0).unwrap();
-                                    let colour = row.get::<String, usize>(1).unwrap();
-                                    let tag_name = row.get::<String, usize>(2).unwrap();
-
-                                    format!("{id},{colour},{tag_name}")
-                                }
-                            },
-                            tx
-                        )
-                    );
-                    let (tx, dev_log_chain_rx) = mpsc::channel();
-                    let _ = utility_thread.send(
-                        (
-                            UtilityCommand::DBQuery {
-                                statement: String::from(
-                                    "SELECT Distinct tag_1, tag_2 FROM relate_tags, tag WHERE (tag_1 = id AND tag_type = 3) OR (tag_2 = id AND tag_type = 3)"
-                                ),
-                                param: vec![],
-                                map_func: |row| {
-                                    //tag_1,tag_2
-                                    let tag_1 = row.get::<i32, usize>(0).unwrap();
-                                    let tag_2 = row.get::<i32, usize>(1).unwrap();
-
-                                    format!("{tag_1},{tag_2}")
-                                }
-                            },
-                            tx
-                        )
-                    );
-                    let (tx, dev_log_rx) = mpsc::channel();
-                    let _ = utility_thread.send(
-                        (
-                            UtilityCommand::DBQuery {
-                                statement: String::from(
-                                    "SELECT id, tag_name, created, body FROM dev_log, tag WHERE id=tag_id"
-                                ),
-                                param: vec![],
-                                map_func: |row| {
-                                    //id,tag_name,created,body
-                                    let id = row.get::<i32, usize>(0).unwrap();
-                                    let name = row.get::<String, usize>(1).unwrap();
-                                    let created = match row.get::<Option<mysql::Value>, usize>(2).unwrap() {
-                                        Some(mysql::Value::Date(year, month, day, _, _, _, _)) => format!("{year}-{month}-{day}"),
-                                        Some(_) => panic!(),
-                                        None => String::from(NULL),
-                                    };
-                                    let body = row.get::<String, usize>(3).unwrap();
-
-                                    format!("{id},{name},{created},{body}")
-                                }
-                            },
-                            tx
-                        )
-                    );
+/// Whether the file is built by the server instead of read from the host path
+fn is_generated(file: &str) -> bool {
+    feed::route(file).is_some() || sitemap::route(file).is_some() || search_route(file).is_some()
+}
 
-                    //initialize dev log page
-                    let file_content = match dev_log_template_rx.recv() {
-                        Ok(Ok(UtilityData::String(template))) => template[0].clone(),
-                        Err(err) => {
-                            error!("{err:?}");
-                            return Err(ResponseStatusCode::InternalServerError);
-                        }
-                        _ => return Err(ResponseStatusCode::InternalServerError),
-                    };
+/// Query parameters of `search?q={query}[&tag={tag}...][&offset={n}][&limit={n}]`
+fn search_route(file: &str) -> Option<Vec<(String, String)>> {
+    let (path, query) = file.split_once('?').unwrap_or((file, ""));
 
-                    // "{id}, {first_push}, {last_push}, {name}, {body}, {repo}"
-                    let regex: Regex = Regex::new("\\{start\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, proj.1);
-                    let regex: Regex = Regex::new("\\{update\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, proj.2);
-
-                    let regex: Regex = Regex::new("\\{project_name\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, proj.3);
-
-                    let regex: Regex = Regex::new("\\{summary\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, proj.4);
-
-                    let regex: Regex = Regex::new("\\{link\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, proj.5);
-
-                    let regex: Regex = Regex::new("\\{tags\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, {
-                        let data = match related_tags_rx.recv() {
-                            Ok(Ok(UtilityData::String(related))) => related,
-                            Err(err) => {
-                                error!("{err:?}");
-                                return Err(ResponseStatusCode::InternalServerError)
-                            },
-                            _ => return Err(ResponseStatusCode::InternalServerError),
-                        };
-
-                        data.iter()
-                            //convert Vec<String> -> Iter<Vec<String>>
-                            .map(|row| {
-                                return row.split(",")
-                                    .map(|val| String::from(val.trim()))
-                                    .collect::<Vec<String>>()
-                            })
-                            //convert Iter<String> -> Iter<(String, (RGB, RGB), String)>
-                            .map(
-                            |row| {
-                                //id,colour,tag_name
-                                let colour = row[1].clone();
-                                let border_colour = Color::new(&format!("#{}", colour)).unwrap();
-                                let border_colour = (border_colour.red as f64, border_colour.green as f64, border_colour.blue as f64);
-                                let bg_colour = (
-                                    calculate_colour(border_colour.0, 0f64, 0.75f64),
-                                    calculate_colour(border_colour.1, 0f64, 0.75f64),
-                                    calculate_colour(border_colour.2, 0f64, 0.75f64),
-                                );
-
-                                (row[0].clone(), (border_colour, bg_colour), row[2].clone())
-                            }
-                        )
-                            //convert Iter<(String, (RGB, RGB), String)> -> Iter<String> #html string
-                            .map(|(id,(border_colour, background_colour),tag_name)| {
-                                format!(
-                                    "<div id=\"{id}\" class=\"tag\" style=\"border-color:rgb({},{},{});background-color:rgb({},{},{});\">{tag_name}</div>",
-                                    border_colour.0, border_colour.1, border_colour.2,
-                                    background_colour.0, background_colour.1, background_colour.2,
-                            )
-                            })
-                            // Iter<String> -> String
-                            .fold(
-                                String::new(),
-                                |mut tag_list, tag| format!("{tag_list}{tag}")
-                            )
-                    });
-
-                    let regex: Regex = Regex::new("\\{dev_logs\\}").unwrap();
-                    let file_content = regex.replace_all(&file_content, {
-                        let template = match dev_log_article_template_rx.recv() {
-                            Ok(Ok(UtilityData::String(template))) => template[0].clone(),
-                            Err(err) => {
-                                error!("{err:?}");
-                                return Err(ResponseStatusCode::InternalServerError);
-                            }
-                            _ => return Err(ResponseStatusCode::InternalServerError),
-                        };
-
-                        let id = &proj.0;
-                        let id = id.parse::<u32>().unwrap();
-                        let dev_log_tree: Graph<NodeIndex, NodeIndex, Directed> = {
-                            let mut graph = Graph::new();
-
-                            let dev_log_chain = match dev_log_chain_rx.recv() {
-                                Ok(Ok(UtilityData::String(related))) => related,
-                                Err(err) => {
-                                    error!("{err:?}");
-                                    return Err(ResponseStatusCode::InternalServerError);
-                                }
-                                _ => return Err(ResponseStatusCode::InternalServerError),
-                            }
-                            .iter()
-                            .map(|row| row.split(",").map(|val| String::from(val.trim())))
-                            .map(|val| val.map(|val| val.parse::<i32>().unwrap() as NodeIndex))
-                            .map(|mut val| (val.next().unwrap(), val.next().unwrap()))
-                            .collect::<Vec<(NodeIndex, NodeIndex)>>();
-
-                            graph.extend_with_edges(dev_log_chain);
-
-                            graph
-                        };
-
-                        type Name = String;
-                        type CreatedDate = Option<String>;
-                        type Content = String;
-
-                        type Article = (Name, CreatedDate, Content);
-
-                        let dev_logs: HashMap<i32, Article> = HashMap::from_iter(
-                            match dev_log_rx.recv() {
-                                Ok(Ok(UtilityData::String(related))) => related,
-                                Err(err) => {
-                                    error!("{err:?}");
-                                    return Err(ResponseStatusCode::InternalServerError);
-                                }
-                                _ => return Err(ResponseStatusCode::InternalServerError),
-                            }
-                            .iter()
-                            .map(|row| {
-                                row.split(",")
-                                    .map(|val| String::from(val.trim()))
-                                    .collect::<Vec<String>>()
-                            })
-                            .map(|row| -> (i32, Article) {
-                                let created = if &row[2] == NULL {
-                                    None
-                                } else {
-                                    Some(row[2].clone())
-                                };
-
-                                return (
-                                    row[0].parse::<i32>().unwrap(),
-                                    (row[1].clone(), created, row[3].clone()),
-                                );
-                            }),
-                        );
-
-                        trace!("dev log");
-                        let mut articles: Vec<String> = Vec::new();
-
-                        let start_node =
-                            dev_log_tree.neighbors_directed(id.into(), Outgoing).next();
-
-                        if let Some(node) = start_node {
-                            let mut node = node;
-
-                            while let Some(next_node) =
-                                dev_log_tree.neighbors_directed(node, Outgoing).next()
-                            {
-                                let id = next_node.index();
-
-                                let (title, time_stamp, content) =
-                                    dev_logs.get(&(id as i32)).unwrap();
-
-                                articles.push({
-                                    let file_content = template.clone();
-
-                                    let regex: Regex = Regex::new("\\{id\\}").unwrap();
-                                    let file_content = regex.replace_all(
-                                        &file_content,
-                                        format!("{}-{}", title.replace(" ", "_"), id),
-                                    );
-
-                                    let regex: Regex = Regex::new("\\{title\\}").unwrap();
-                                    let file_content = regex.replace_all(&file_content, title);
-
-                                    let regex: Regex = Regex::new("\\{update\\}").unwrap();
-                                    let file_content = regex.replace_all(&file_content, {
-                                        match time_stamp {
-                                            Some(val) => val,
-                                            None => "",
-                                        }
-                                    });
-
-                                    let regex: Regex = Regex::new("\\{tags\\}").unwrap();
-                                    let file_content = regex.replace_all(&file_content, "");
-
-                                    let regex: Regex = Regex::new("\\{content\\}").unwrap();
-                                    let file_content = regex.replace_all(&file_content, {
-                                        let mut options = Options::empty();
-                                        options.insert(Options::ENABLE_STRIKETHROUGH);
-                                        let parser = Parser::new_ext(content, options);
-
-                                        let mut html_output = String::new();
-                                        html::push_html(&mut html_output, parser);
-
-                                        html_output
-                                    });
-
-                                    file_content.to_string()
-                                });
-
-                                node = next_node;
-                            }
-                        }
-                        articles
-                            .iter()
-                            .rev()
-                            .fold(String::new(), |acc, val| format!("{}{}", acc, val))
-                    });
-
-                    return Ok(
-                        Response {
-                            status: ResponseStatusCode::Ok,
-                            header: HashMap::new(),
-                            body: Some(Body {
-                                content_type: ContentType::Text(Text::html),
-                                content: file_content
-                                    .as_bytes()
-                                    .to_vec(),
-                            })
-                        }
-                    )
-                };
+    if path.trim_matches('/') != "search" {
+        return None;
+    }
+
+    Some(
+        query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, value) = param.split_once('=').unwrap_or((param, ""));
+
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect(),
+    )
+}
+
+/// Decodes `%XX` escapes & `+` of a query string component
+fn percent_decode(value: &str) -> String {
+    url_decode(&value.replace('+', " "))
+}
+
+/// Answers a search with the matching documents as json
+fn search(
+    query: &str,
+    tags: &[String],
+    offset: Option<usize>,
+    limit: Option<usize>,
+    utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let search_setting = &Setting::get().search;
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((UtilityCommand::GetSearchIndex, tx));
+
+    let Ok(Ok(UtilityData::SearchIndex(index))) = rx.recv() else {
+        error!("Search index isn't built");
+        return Err(ResponseStatusCode::ServiceUnavailable);
+    };
+
+    let offset = offset.unwrap_or(0);
+    let limit = limit
+        .unwrap_or(search_setting.results)
+        .min(search_setting.max_results);
+
+    let (total, hits) = index.search(query, tags, offset, limit, search_setting.snippet_words);
+
+    Ok(Response {
+        status: ResponseStatusCode::Ok,
+        header: HashMap::new(),
+        body: Some(Body {
+            content_type: ContentType::Application(Application::json),
+            content: json!({
+                "query": query,
+                "tags": tags,
+                "total": total,
+                "offset": offset,
+                "results": hits,
+            })
+            .to_string()
+            .into_bytes(),
+        }),
+    })
+}
+
+/// Builds the site wide feed, or the feed of a single project's dev logs
+fn get_feed(
+    format: feed::Format,
+    project_slug: Option<&str>,
+    heading: &HashMap<String, String>,
+    setting: &ServerSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let Some(host) = heading.get("host") else {
+        return Err(ResponseStatusCode::ImATeapot);
+    };
+
+    let Some(host_path) = setting.paths.get(host) else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    let feed_setting = &Setting::get().feed;
+    let markdown_setting = Setting::get().markdown(host);
+    let base_url = format!("http://{host}");
+
+    let host_path = Path::new(&host_path.path);
+
+    let mut projects = load_projects(utility_thread)?;
+
+    if let Some(project_slug) = project_slug {
+        projects.retain(|(proj, _)| proj.name.replace(" ", "_") == project_slug);
+
+        if projects.is_empty() {
+            return Err(ResponseStatusCode::NotFound);
+        }
+    }
+
+    // undated entries are published with the entry before them
+    let mut dated: Vec<(SystemTime, &Project, &DevLog)> = Vec::new();
+    for (proj, chain) in &projects {
+        let mut published = proj.first_push;
+        for dev_log in chain {
+            published = dev_log.created.unwrap_or(published);
+
+            dated.push((published.to_system_time(), proj, dev_log));
+        }
+    }
+
+    // only the entries in the feed are rendered
+    dated.sort_by(|(published_1, ..), (published_2, ..)| published_2.cmp(published_1));
+    dated.truncate(feed_setting.limit);
+
+    let entries: Vec<feed::Entry> = dated
+        .into_iter()
+        .map(|(published, proj, dev_log)| {
+            let project_slug = proj.name.replace(" ", "_");
+
+            // feed readers don't resolve root relative urls
+            let mut renderer = Renderer::new(&markdown_setting)
+                .images(host_path)
+                .absolute(&base_url);
+
+            feed::Entry {
+                id: format!("{base_url}/dev_log/{}", dev_log.id),
+                title: dev_log.name.clone(),
+                link: format!("{base_url}/{project_slug}/{}", dev_log.slug()),
+                published: Some(published),
+                category: proj.name.clone(),
+                content: dev_log_html(&project_slug, dev_log, &mut renderer).html,
             }
-            Err(err) => error!("{err:?}"),
-            Ok(_) => {}
-        };
+        })
+        .collect();
+
+    let updated = entries.iter().filter_map(|entry| entry.published).max();
+
+    let (title, link, url) = match project_slug {
+        Some(project_slug) => (
+            format!("{} - {}", feed_setting.title, projects[0].0.name),
+            format!("{base_url}/{project_slug}/"),
+            format!("{base_url}/{project_slug}/feed.xml"),
+        ),
+        None => (
+            feed_setting.title.clone(),
+            format!("{base_url}/"),
+            format!(
+                "{base_url}/feed.{}",
+                match format {
+                    feed::Format::Rss => "xml",
+                    feed::Format::Atom => "atom",
+                    feed::Format::Json => "json",
+                }
+            ),
+        ),
+    };
+
+    let content = feed::Feed {
+        title,
+        description: feed_setting.description.clone(),
+        author: feed_setting.author.clone(),
+        link,
+        url,
+        updated,
+        entries,
+    }
+    .render(format);
+
+    let mut response = Response {
+        status: ResponseStatusCode::Ok,
+        header: HashMap::new(),
+        body: Some(Body {
+            content_type: match format {
+                feed::Format::Rss | feed::Format::Atom => {
+                    ContentType::Application(Application::xml)
+                }
+                feed::Format::Json => ContentType::Application(Application::json),
+            },
+            content: content.into_bytes(),
+        }),
+    };
+
+    if let Some(updated) = updated {
+        conditional::add_last_modified(&mut response, updated);
+    }
+
+    Ok(response)
+}
+
+/// Serves the host's sitemap, which becomes a sitemap index once there are more urls than a
+/// single sitemap can list. Cached alongside rendered pages.
+fn get_sitemap(
+    part: Option<usize>,
+    heading: &HashMap<String, String>,
+    setting: &ServerSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let Some(host) = heading.get("host") else {
+        return Err(ResponseStatusCode::ImATeapot);
+    };
+
+    let Some(host_setting) = setting.paths.get(host) else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    let slug = match part {
+        Some(part) => format!("{}/sitemap-{part}.xml", host_setting.path),
+        None => format!("{}/sitemap.xml", host_setting.path),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((UtilityCommand::GetPage { slug: slug.clone() }, tx));
+
+    let page = match rx.recv() {
+        Ok(Ok(UtilityData::Page(Some(page)))) => {
+            trace!("cached sitemap: {slug}");
+
+            page
+        }
+        _ => {
+            let base_url = format!("http://{host}");
+            let urls = sitemap_urls(
+                &host_setting.path,
+                &host_setting.allow,
+                &base_url,
+                utility_thread,
+            )?;
+
+            let content = match part {
+                None if sitemap::parts(urls.len()) > 1 => sitemap::index(&base_url, &urls),
+                None => sitemap::urlset(&urls, 1).ok_or(ResponseStatusCode::NotFound)?,
+                Some(part) if sitemap::parts(urls.len()) > 1 => {
+                    sitemap::urlset(&urls, part).ok_or(ResponseStatusCode::NotFound)?
+                }
+                // a single sitemap isn't split into parts
+                Some(_) => return Err(ResponseStatusCode::NotFound),
+            };
+
+            let page = Page {
+                content: content.into_bytes(),
+                last_modified: urls.iter().filter_map(|url| url.lastmod).max(),
+            };
+
+            let (tx, _rx) = mpsc::channel();
+            let _ = utility_thread.send((
+                UtilityCommand::CachePage {
+                    slug,
+                    page: page.clone(),
+                },
+                tx,
+            ));
+
+            page
+        }
+    };
+
+    let mut response = Response {
+        status: ResponseStatusCode::Ok,
+        header: HashMap::new(),
+        body: Some(Body {
+            content_type: ContentType::Application(Application::xml),
+            content: page.content,
+        }),
+    };
+
+    if let Some(last_modified) = page.last_modified {
+        conditional::add_last_modified(&mut response, last_modified);
+    }
+
+    Ok(response)
+}
+
+/// Static `.html` files of the host (if allowed), project pages, dev log permalinks & tag pages
+fn sitemap_urls(
+    host_path: &str,
+    allow: &[String],
+    base_url: &str,
+    utility_thread: &UtilitySender,
+) -> Result<Vec<sitemap::Url>, ResponseStatusCode> {
+    let (tx, tags_rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::DBQuery {
+            statement: String::from(SELECT_TAGS),
+            param: Vec::new(),
+            table: Table::Tags,
+        },
+        tx,
+    ));
+
+    let mut urls: Vec<sitemap::Url> = Vec::new();
+
+    if allow.iter().any(|allowed| allowed == "html") {
+        let mut files = Vec::new();
+        find_files(Path::new(host_path), &mut files);
+        files.sort();
+
+        for file in files {
+            if file.extension().and_then(|ext| ext.to_str()) != Some("html") {
+                continue;
+            }
+
+            let Ok(relative) = file.strip_prefix(host_path) else {
+                continue;
+            };
+
+            let relative = relative.to_string_lossy().replace("\\", "/");
+            let relative = match relative.strip_suffix("index.html") {
+                Some(dir) => dir,
+                None => &relative,
+            };
+
+            urls.push(sitemap::Url {
+                loc: format!("{base_url}/{relative}"),
+                lastmod: fs::metadata(&file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok(),
+            });
+        }
+    }
+
+    let projects = load_projects(utility_thread)?;
+
+    for (proj, chain) in &projects {
+        let project_slug = proj.name.replace(" ", "_");
+
+        let last_modified = chain
+            .iter()
+            .filter_map(|dev_log| dev_log.created)
+            .chain([proj.last_push.unwrap_or(proj.first_push)])
+            .max();
+
+        urls.push(sitemap::Url {
+            loc: format!("{base_url}/{project_slug}/"),
+            lastmod: last_modified.map(|date| date.to_system_time()),
+        });
+
+        for dev_log in chain {
+            urls.push(sitemap::Url {
+                loc: format!("{base_url}/{project_slug}/{}", dev_log.slug()),
+                lastmod: dev_log.created.map(|date| date.to_system_time()),
+            });
+        }
+    }
+
+    let Ok(Ok(UtilityData::Tags(tags))) = tags_rx.recv() else {
+        return Err(ResponseStatusCode::InternalServerError);
+    };
+
+    for tag in tags
+        .iter()
+        .filter(|tag| !projects.iter().any(|(proj, _)| proj.id == tag.id))
+    {
+        urls.push(sitemap::Url {
+            loc: format!("{base_url}{}", tag.url()),
+            lastmod: None,
+        });
+    }
+
+    // a project may also have a static page
+    let mut seen = HashSet::new();
+    urls.retain(|url| seen.insert(url.loc.clone()));
+
+    Ok(urls)
+}
+
+/// Renders the project page at `{host}/{project_name}/index.html`, the dev log permalink at
+/// `{host}/{project_name}/{dev_log_slug}/index.html` or the tag page at
+/// `{host}/tag/{tag_name}/index.html`, or serves it from the page cache
+fn get_project_page(
+    host_path: &str,
+    file: &Path,
+    markdown_setting: &MarkdownSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let slug = file.to_string_lossy().to_string();
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((UtilityCommand::GetPage { slug: slug.clone() }, tx));
+
+    if let Ok(Ok(UtilityData::Page(Some(page)))) = rx.recv() {
+        trace!("cached page: {slug}");
+
+        return Ok(page_response(page));
+    }
+
+    let page = match page_path(host_path, file) {
+        Some(PagePath::Project(project)) => {
+            render_project_page(host_path, project, markdown_setting, utility_thread)?
+        }
+        Some(PagePath::DevLog(project, dev_log)) => {
+            render_dev_log_page(host_path, project, dev_log, markdown_setting, utility_thread)?
+        }
+        Some(PagePath::Tag(tag)) => {
+            render_tag_page(host_path, tag, markdown_setting, utility_thread)?
+        }
+        None => return Err(ResponseStatusCode::NotFound),
+    };
+
+    let (tx, _rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::CachePage {
+            slug,
+            page: page.clone(),
+        },
+        tx,
+    ));
+
+    Ok(page_response(page))
+}
+
+/// Page rendered from the database, by the slugs in its path
+enum PagePath<'a> {
+    Project(&'a str),
+    DevLog(&'a str, &'a str),
+    Tag(&'a str),
+}
+
+/// Splits `{host}/{project}[/{dev_log}]/index.html` & `{host}/tag/{tag}/index.html` into slugs
+fn page_path<'a>(host_path: &str, file: &'a Path) -> Option<PagePath<'a>> {
+    let parts = file
+        .strip_prefix(host_path)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<&str>>>()?;
+
+    match parts.as_slice() {
+        ["tag", tag, "index.html"] => Some(PagePath::Tag(tag)),
+        [project, "index.html"] => Some(PagePath::Project(project)),
+        [project, dev_log, "index.html"] => Some(PagePath::DevLog(project, dev_log)),
+        _ => None,
+    }
+}
+
+fn page_response(page: Page) -> Response {
+    let mut response = Response {
+        status: ResponseStatusCode::Ok,
+        header: HashMap::new(),
+        body: Some(Body {
+            content_type: ContentType::Text(Text::html),
+            content: page.content,
+        }),
+    };
+
+    if let Some(last_modified) = page.last_modified {
+        conditional::add_last_modified(&mut response, last_modified);
+    }
+
+    response
+}
+
+/// Rows a project page is rendered from
+struct ProjectData {
+    project: Project,
+    tags: Vec<Tag>,
+    /// Dev logs in the order of the chain, oldest first
+    dev_logs: Vec<DevLog>,
+    /// Skill tags of each dev log
+    article_tags: HashMap<i32, Vec<Tag>>,
+}
+
+impl ProjectData {
+    /// Path segment of the project, e.g. `Portfolio_Server`
+    fn slug(&self) -> String {
+        self.project.name.replace(" ", "_")
+    }
+
+    fn article_tags(&self, dev_log: &DevLog) -> &[Tag] {
+        self.article_tags
+            .get(&dev_log.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        let last_modified = Some(self.project.last_push.unwrap_or(self.project.first_push));
+
+        self.dev_logs
+            .iter()
+            .fold(last_modified, |last_modified, dev_log| {
+                last_modified.max(dev_log.created)
+            })
+            .map(|last_modified| last_modified.to_system_time())
+    }
+}
+
+/// Loads the project whose slug matches along with its tags & dev log chain.
+/// `None` if there is no such project.
+fn load_project(
+    project_slug: &str,
+    utility_thread: &UtilitySender,
+) -> Result<Option<ProjectData>, ResponseStatusCode> {
+    let Some((proj, chain)) = load_projects(utility_thread)?
+        .into_iter()
+        .find(|(proj, _)| proj.name.replace(" ", "_") == project_slug)
+    else {
+        return Ok(None);
+    };
+
+    //query db
+    let (tx, related_tags_rx) = mpsc::channel();
+    let _ = utility_thread.send(
+        (
+            UtilityCommand::DBQuery {
+                statement: String::from(
+                    "SELECT id, colour, tag_name, tag_type FROM tag WHERE EXISTS(SELECT * FROM relate_tags WHERE relate_tags.tag_1 = ? AND relate_tags.tag_2 = tag.id) AND tag_type != 3"
+                ),
+                param: vec![proj.id.to_string()],
+                table: Table::Tags,
+            },
+            tx
+        )
+    );
+
+    let article_tags = load_article_tags(
+        &chain.iter().map(|dev_log| dev_log.id).collect::<Vec<i32>>(),
+        utility_thread,
+    )?;
+
+    let tags = match related_tags_rx.recv() {
+        Ok(Ok(UtilityData::Tags(related))) => related,
+        Err(err) => {
+            error!("{err:?}");
+            return Err(ResponseStatusCode::InternalServerError);
+        }
+        _ => return Err(ResponseStatusCode::InternalServerError),
+    };
+
+    Ok(Some(ProjectData {
+        project: proj,
+        tags,
+        dev_logs: chain,
+        article_tags,
+    }))
+}
+
+/// Skill tags of each dev log, fetched in a single query
+fn load_article_tags(
+    dev_logs: &[i32],
+    utility_thread: &UtilitySender,
+) -> Result<HashMap<i32, Vec<Tag>>, ResponseStatusCode> {
+    if dev_logs.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::DBQuery {
+            statement: format!(
+                "SELECT relate_tags.tag_1, tag.id, tag.colour, tag.tag_name, tag.tag_type FROM relate_tags, tag WHERE relate_tags.tag_2 = tag.id AND tag.tag_type != 3 AND relate_tags.tag_1 IN ({})",
+                vec!["?"; dev_logs.len()].join(", ")
+            ),
+            param: dev_logs.iter().map(|id| id.to_string()).collect(),
+            table: Table::ArticleTags,
+        },
+        tx,
+    ));
+
+    let article_tags = match rx.recv() {
+        Ok(Ok(UtilityData::ArticleTags(article_tags))) => article_tags,
+        Err(err) => {
+            error!("{err:?}");
+            return Err(ResponseStatusCode::InternalServerError);
+        }
+        _ => return Err(ResponseStatusCode::InternalServerError),
+    };
+
+    let mut tags: HashMap<i32, Vec<Tag>> = HashMap::new();
+    for article_tag in article_tags {
+        tags.entry(article_tag.dev_log).or_default().push(article_tag.tag);
+    }
+
+    Ok(tags)
+}
+
+/// [`model::load_projects`] for a request
+fn load_projects(
+    utility_thread: &UtilitySender,
+) -> Result<Vec<(Project, Vec<DevLog>)>, ResponseStatusCode> {
+    model::load_projects(utility_thread).map_err(|()| ResponseStatusCode::InternalServerError)
+}
+
+/// Template data shared by the project page & dev log permalinks
+fn project_value(data: &ProjectData) -> Result<TemplateValue, ResponseStatusCode> {
+    let proj = &data.project;
+
+    let mut page = TemplateValue::map([
+        ("start", proj.first_push.to_string().into()),
+        (
+            "update",
+            match proj.last_push {
+                Some(last_push) => last_push.to_string(),
+                None => String::from(NULL),
+            }
+            .into(),
+        ),
+        ("ongoing", proj.last_push.is_none().into()),
+        ("project_name", (&proj.name).into()),
+        ("project_url", format!("/{}/", data.slug()).into()),
+        ("summary", (&proj.summary).into()),
+        ("link", (&proj.repo).into()),
+    ]);
+
+    insert_tags(&mut page, &data.tags)?;
+
+    Ok(page)
+}
+
+/// Adds the tags as `tags`, rendered by the built-in tag list, & as data under `tag_list`
+fn insert_tags(value: &mut TemplateValue, tags: &[Tag]) -> Result<(), ResponseStatusCode> {
+    let tag_list = tags.iter().map(tag_value).collect::<Vec<TemplateValue>>();
+
+    let tags = TemplateValue::map([("tags", tag_list.clone().into())]);
+    value.insert(
+        "tags",
+        TemplateValue::Html(render(tag_list_template(), &tags)?),
+    );
+    value.insert("tag_list", tag_list.into());
+
+    Ok(())
+}
+
+/// Template data linking to a dev log, `{id}`, `{url}` & `{title}`
+fn link_value(project_slug: &str, dev_log: &DevLog) -> TemplateValue {
+    TemplateValue::map([
+        ("id", dev_log.slug().into()),
+        (
+            "url",
+            format!("/{project_slug}/{}", dev_log.slug()).into(),
+        ),
+        ("title", (&dev_log.name).into()),
+    ])
+}
+
+/// Template data of a dev log, with its content rendered by [`dev_log_html`]
+fn article_value(
+    project_slug: &str,
+    dev_log: &DevLog,
+    tags: &[Tag],
+    content: Rendered,
+) -> Result<TemplateValue, ResponseStatusCode> {
+    let mut article = link_value(project_slug, dev_log);
+
+    article.insert(
+        "update",
+        match dev_log.created {
+            Some(val) => val.to_string(),
+            None => String::new(),
+        }
+        .into(),
+    );
+    article.insert("stats", stats_value(&content.stats));
+    // markdown output is the only database value written without escaping
+    article.insert("content", TemplateValue::Html(content.html));
+    article.insert("toc", TemplateValue::Html(markdown::toc(&content.headings)));
+
+    insert_tags(&mut article, tags)?;
+
+    Ok(article)
+}
+
+/// `{stats.words}`, `{stats.reading_time}` (minutes), `{stats.code_blocks}` & `{stats.images}`
+fn stats_value(stats: &Stats) -> TemplateValue {
+    TemplateValue::map([
+        ("words", stats.words.into()),
+        ("reading_time", stats.reading_minutes().into()),
+        ("code_blocks", stats.code_blocks.into()),
+        ("images", stats.images.into()),
+    ])
+}
+
+/// Renders the dev log's markdown, sanitized unless its author is trusted
+fn dev_log_html(project_slug: &str, dev_log: &DevLog, renderer: &mut Renderer) -> Rendered {
+    // shown at `/{project}/` & `/{project}/{slug}`, which browsers both resolve relative
+    // urls against `/{project}/`
+    let page = format!("/{project_slug}/");
+    let mut rendered = renderer.render(&dev_log.body, &page);
+
+    if !Setting::get().sanitize.trusted_dev_logs.contains(&dev_log.id) {
+        rendered.html = sanitize(&rendered.html);
+    }
+
+    rendered
+}
+
+fn render_project_page(
+    host_path: &str,
+    project_slug: &str,
+    markdown_setting: &MarkdownSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Page, ResponseStatusCode> {
+    //get template files
+    let dev_log_article_template_rx =
+        request_template(host_path, "dev_log.article.html.template", utility_thread);
+    let dev_log_template_rx = request_template(host_path, "dev_log.html.template", utility_thread);
+
+    let Some(data) = load_project(project_slug, utility_thread)? else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    let mut page = project_value(&data)?;
+
+    let article_template = recv_template(dev_log_article_template_rx)?;
+
+    // rendered oldest first for stable anchors, shown newest first
+    let mut renderer = Renderer::new(markdown_setting).images(Path::new(host_path));
+    let contents = data
+        .dev_logs
+        .iter()
+        .map(|dev_log| dev_log_html(&data.slug(), dev_log, &mut renderer))
+        .collect::<Vec<Rendered>>();
+
+    let stats: Stats = contents.iter().map(|content| content.stats).sum();
+    page.insert("stats", stats_value(&stats));
+
+    let mut headings = Vec::new();
+    let articles = data
+        .dev_logs
+        .iter()
+        .zip(contents)
+        .rev()
+        .map(|(dev_log, content)| {
+            headings.extend(content.headings.iter().cloned());
+
+            article_value(&data.slug(), dev_log, data.article_tags(dev_log), content)
+        })
+        .collect::<Result<Vec<TemplateValue>, ResponseStatusCode>>()?;
+
+    page.insert("toc", TemplateValue::Html(markdown::toc(&headings)));
+
+    page.insert(
+        "dev_logs",
+        TemplateValue::Html(
+            articles
+                .iter()
+                .map(|article| render(&article_template, article))
+                .collect::<Result<String, ResponseStatusCode>>()?,
+        ),
+    );
+    page.insert("articles", articles.into());
+
+    let file_content = render(&*recv_template(dev_log_template_rx)?, &page)?;
+
+    Ok(Page {
+        content: file_content.into_bytes(),
+        last_modified: data.last_modified(),
+    })
+}
+
+/// Renders a single dev log with `dev_log.entry.html.template`.
+///
+/// Besides the project's data, the template gets `dev_log` & its `toc` along with the
+/// `previous` & `next` entries of the chain (null at either end).
+fn render_dev_log_page(
+    host_path: &str,
+    project_slug: &str,
+    entry_slug: &str,
+    markdown_setting: &MarkdownSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Page, ResponseStatusCode> {
+    let template_rx = request_template(host_path, "dev_log.entry.html.template", utility_thread);
+
+    let Some(data) = load_project(project_slug, utility_thread)? else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    let Some(index) = data
+        .dev_logs
+        .iter()
+        .position(|dev_log| dev_log.slug() == entry_slug)
+    else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    let dev_log = &data.dev_logs[index];
+
+    // anchors match the project page's, which renders the whole chain
+    let mut renderer = Renderer::new(markdown_setting).images(Path::new(host_path));
+    let mut stats: Stats = data.dev_logs[..index]
+        .iter()
+        .map(|earlier| renderer.skip(&earlier.body))
+        .sum();
+
+    let content = dev_log_html(&data.slug(), dev_log, &mut renderer);
+    stats += content.stats;
+
+    let toc = markdown::toc(&content.headings);
+    let dev_log_value = article_value(&data.slug(), dev_log, data.article_tags(dev_log), content)?;
+
+    // stats of the whole chain, like the project page
+    stats += data.dev_logs[index + 1..]
+        .iter()
+        .map(|later| renderer.skip(&later.body))
+        .sum::<Stats>();
+
+    let neighbour = |index: Option<usize>| {
+        index
+            .and_then(|index| data.dev_logs.get(index))
+            .map(|dev_log| link_value(&data.slug(), dev_log))
+    };
+
+    let previous = neighbour(index.checked_sub(1));
+    let next = neighbour(Some(index + 1));
+
+    let mut page = project_value(&data)?;
+    page.insert("stats", stats_value(&stats));
+    page.insert("dev_log", dev_log_value);
+    page.insert("toc", TemplateValue::Html(toc));
+    page.insert("previous", previous.into());
+    page.insert("next", next.into());
+
+    let file_content = render(&*recv_template(template_rx)?, &page)?;
+
+    let last_modified = Some(data.project.last_push.unwrap_or(data.project.first_push))
+        .max(dev_log.created)
+        .map(|last_modified| last_modified.to_system_time());
+
+    Ok(Page {
+        content: file_content.into_bytes(),
+        last_modified,
+    })
+}
+
+/// Renders `tag.html.template` for the tag.
+///
+/// The template gets `tag`, the `projects` & `dev_logs` related to it with their counts,
+/// and `related_tags`, the other tags of those projects & dev logs, most shared first.
+fn render_tag_page(
+    host_path: &str,
+    slug: &str,
+    markdown_setting: &MarkdownSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Page, ResponseStatusCode> {
+    let template_rx = request_template(host_path, "tag.html.template", utility_thread);
+
+    let (tx, tags_rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::DBQuery {
+            statement: String::from(SELECT_TAGS),
+            param: Vec::new(),
+            table: Table::Tags,
+        },
+        tx,
+    ));
+    let (tx, related_rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::DBQuery {
+            statement: String::from(SELECT_RELATED),
+            param: Vec::new(),
+            table: Table::Relations,
+        },
+        tx,
+    ));
+
+    let projects = load_projects(utility_thread)?;
+
+    let Ok(Ok(UtilityData::Tags(tags))) = tags_rx.recv() else {
+        return Err(ResponseStatusCode::InternalServerError);
+    };
+    let Ok(Ok(UtilityData::Relations(related))) = related_rx.recv() else {
+        return Err(ResponseStatusCode::InternalServerError);
+    };
+
+    let is_project = |id: i32| projects.iter().any(|(proj, _)| proj.id == id);
+
+    let slug = percent_decode(slug);
+    let Some(tag) = tags
+        .iter()
+        .find(|tag| !is_project(tag.id) && tag.slug() == slug)
+    else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    let is_related = |id: i32| {
+        related.iter().any(|relation| {
+            (relation.tag_1 == id && relation.tag_2 == tag.id)
+                || (relation.tag_1 == tag.id && relation.tag_2 == id)
+        })
+    };
+
+    let related_projects: Vec<&Project> = projects
+        .iter()
+        .map(|(proj, _)| proj)
+        .filter(|proj| is_related(proj.id))
+        .collect();
+
+    // newest first
+    let mut related_dev_logs: Vec<(&Project, &DevLog)> = projects
+        .iter()
+        .flat_map(|(proj, chain)| chain.iter().map(move |dev_log| (proj, dev_log)))
+        .filter(|(_, dev_log)| is_related(dev_log.id))
+        .collect();
+    related_dev_logs.sort_by(|(_, dev_log_1), (_, dev_log_2)| {
+        dev_log_2.created.cmp(&dev_log_1.created)
+    });
+
+    // other tags of the related projects & dev logs, by how many of them share it
+    let mut shared: HashMap<i32, usize> = HashMap::new();
+    for id in related_projects
+        .iter()
+        .map(|proj| proj.id)
+        .chain(related_dev_logs.iter().map(|(_, dev_log)| dev_log.id))
+    {
+        for relation in related.iter().filter(|relation| relation.tag_1 == id) {
+            if relation.tag_2 != tag.id && relation.tag_2 != id && !is_project(relation.tag_2) {
+                *shared.entry(relation.tag_2).or_default() += 1;
+            }
+        }
+    }
+
+    let mut related_tags: Vec<(&Tag, usize)> = tags
+        .iter()
+        .filter_map(|tag| shared.get(&tag.id).map(|count| (tag, *count)))
+        .collect();
+    related_tags.sort_by(|(tag_1, count_1), (tag_2, count_2)| {
+        count_2.cmp(count_1).then(tag_1.name.cmp(&tag_2.name))
+    });
+
+    let project_values = related_projects
+        .iter()
+        .map(|proj| {
+            TemplateValue::map([
+                ("project_name", (&proj.name).into()),
+                ("project_url", format!("/{}/", proj.name.replace(" ", "_")).into()),
+                ("summary", (&proj.summary).into()),
+                ("start", proj.first_push.to_string().into()),
+                (
+                    "update",
+                    match proj.last_push {
+                        Some(last_push) => last_push.to_string(),
+                        None => String::from(NULL),
+                    }
+                    .into(),
+                ),
+                ("ongoing", proj.last_push.is_none().into()),
+            ])
+        })
+        .collect::<Vec<TemplateValue>>();
+
+    let article_tags = load_article_tags(
+        &related_dev_logs
+            .iter()
+            .map(|(_, dev_log)| dev_log.id)
+            .collect::<Vec<i32>>(),
+        utility_thread,
+    )?;
+
+    let mut renderer = Renderer::new(markdown_setting).images(Path::new(host_path));
+    let dev_log_values = related_dev_logs
+        .iter()
+        .map(|(proj, dev_log)| {
+            let project_slug = proj.name.replace(" ", "_");
+
+            let mut article = article_value(
+                &project_slug,
+                dev_log,
+                article_tags
+                    .get(&dev_log.id)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+                dev_log_html(&project_slug, dev_log, &mut renderer),
+            )?;
+            article.insert("project_name", (&proj.name).into());
+            article.insert("project_url", format!("/{project_slug}/").into());
+
+            Ok(article)
+        })
+        .collect::<Result<Vec<TemplateValue>, ResponseStatusCode>>()?;
+
+    let related_tag_values = related_tags
+        .iter()
+        .map(|(tag, count)| {
+            let mut value = tag_value(tag);
+            value.insert("count", (*count).into());
+
+            value
+        })
+        .collect::<Vec<TemplateValue>>();
+
+    let related_tag_list = TemplateValue::map([("tags", related_tag_values.clone().into())]);
+
+    let page = TemplateValue::map([
+        ("tag", tag_value(tag)),
+        ("project_count", project_values.len().into()),
+        ("dev_log_count", dev_log_values.len().into()),
+        ("projects", project_values.into()),
+        ("dev_logs", dev_log_values.into()),
+        (
+            "tags",
+            TemplateValue::Html(render(tag_list_template(), &related_tag_list)?),
+        ),
+        ("related_tags", related_tag_values.into()),
+    ]);
+
+    let file_content = render(&*recv_template(template_rx)?, &page)?;
+
+    let last_modified = related_projects
+        .iter()
+        .map(|proj| Some(proj.last_push.unwrap_or(proj.first_push)))
+        .chain(related_dev_logs.iter().map(|(_, dev_log)| dev_log.created))
+        .max()
+        .flatten()
+        .map(|last_modified| last_modified.to_system_time());
+
+    Ok(Page {
+        content: file_content.into_bytes(),
+        last_modified,
+    })
+}
+
+/// Asks the utility thread for a compiled template under `{host}/src/template`
+fn request_template(
+    host_path: &str,
+    name: &str,
+    utility_thread: &UtilitySender,
+) -> mpsc::Receiver<Result<UtilityData, ()>> {
+    let mut file = PathBuf::from(host_path);
+    file.push("src");
+    file.push("template");
+    file.push(name);
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((UtilityCommand::GetTemplate { file }, tx));
+
+    rx
+}
+
+fn recv_template(
+    rx: mpsc::Receiver<Result<UtilityData, ()>>,
+) -> Result<Arc<Template>, ResponseStatusCode> {
+    match rx.recv() {
+        Ok(Ok(UtilityData::Template(template))) => Ok(template),
+        Err(err) => {
+            error!("{err:?}");
+            Err(ResponseStatusCode::InternalServerError)
+        }
+        _ => Err(ResponseStatusCode::InternalServerError),
+    }
+}
+
+fn render(template: &Template, data: &TemplateValue) -> Result<String, ResponseStatusCode> {
+    template.render(data).map_err(|err| {
+        error!("{err}");
+        ResponseStatusCode::InternalServerError
+    })
+}
+
+/// Markup of `{tags}`
+fn tag_list_template() -> &'static Template {
+    static TAG_LIST: OnceLock<Template> = OnceLock::new();
+
+    TAG_LIST.get_or_init(|| {
+        Template::compile(
+            "tag_list",
+            "{#for tag in tags}<a href=\"{tag.url}\"><div id=\"{tag.id}\" class=\"tag\" style=\"border-color:rgb({tag.border});background-color:rgb({tag.background});\">{tag.name}</div></a>{/for}",
+        )
+        .unwrap()
+    })
+}
+
+/// Template data of a tag, with the border & background colours shown on pages
+fn tag_value(tag: &Tag) -> TemplateValue {
+    let border_colour: RGB = match Color::new(&format!("#{}", tag.colour)) {
+        Ok(colour) => (colour.red as f64, colour.green as f64, colour.blue as f64),
+        Err(_) => {
+            warn!("Tag {} has an invalid colour {:?}", tag.id, tag.colour);
+            DEFAULT_TAG_COLOUR
+        }
+    };
+    let background_colour: RGB = (
+        calculate_colour(border_colour.0, 0f64, 0.75f64),
+        calculate_colour(border_colour.1, 0f64, 0.75f64),
+        calculate_colour(border_colour.2, 0f64, 0.75f64),
+    );
+
+    TemplateValue::map([
+        ("id", tag.id.into()),
+        ("name", (&tag.name).into()),
+        ("url", tag.url().into()),
+        ("colour", format!("#{}", tag.colour).into()),
+        (
+            "border",
+            format!("{},{},{}", border_colour.0, border_colour.1, border_colour.2).into(),
+        ),
+        (
+            "background",
+            format!(
+                "{},{},{}",
+                background_colour.0, background_colour.1, background_colour.2
+            )
+            .into(),
+        ),
+    ])
+}
+
+/// Answers with the same status & headers as [`get`] but no body.
+///
+/// Headers of static files are built from file metadata. Anything whose
+/// length depends on compression or rendering goes through [`get`].
+pub fn head(
+    request: &Request,
+    setting: &ServerSetting,
+    utility_thread: &UtilitySender,
+) -> Result<Response, ResponseStatusCode> {
+    let Request(method, heading) = request;
+
+    let Method::Head { file } = method else {
+        panic!()
+    };
+
+    if !is_generated(file) {
+        let (_host_path, path) = resolve_file(file, heading, setting)?;
+
+        if let Some(response) = head_static(heading, &path, utility_thread) {
+            if conditional::is_not_modified(heading, &response) {
+                return Ok(conditional::not_modified(response));
+            }
+
+            return Ok(response);
+        }
+    }
+
+    let request = Request(Method::Get { file: file.clone() }, heading.clone());
+
+    Ok(without_body(get(&request, setting, utility_thread)?))
+}
+
+/// Builds HEAD headers from file metadata.
+/// `None` if the file is missing or `get` would compress it on the fly.
+fn head_static(
+    heading: &HashMap<String, String>,
+    file: &Path,
+    utility_thread: &UtilitySender,
+) -> Option<Response> {
+    let content_type = ContentType::try_from(file.extension()?.to_str()?).ok()?;
+
+    let metadata = |file: PathBuf| file_metadata(file, utility_thread);
+
+    let mut header = HashMap::new();
+
+    // sidecars are only served for a file that still exists
+    let (identity_len, modified, hash) = metadata(file.to_path_buf())?;
+    let mut etag = conditional::etag(&hash);
+
+    let len = match heading.get("accept-encoding") {
+        Some(accept_encoding) if compression::is_compressible(&content_type) => {
+            header.insert(String::from("Vary"), String::from("Accept-Encoding"));
+
+            match fresh_sidecar(accept_encoding, file, modified, utility_thread) {
+                Some((encoding, _, (len, _, sidecar_hash))) => {
+                    header.insert(
+                        String::from("Content-Encoding"),
+                        String::from(encoding.token()),
+                    );
+                    etag = conditional::etag(&sidecar_hash);
+
+                    len
+                }
+                None => {
+                    let compressed = compression::choose_for(
+                        heading,
+                        &content_type,
+                        identity_len as usize,
+                        &Setting::get().compression,
+                    )
+                    .is_some();
+                    if compressed {
+                        return None;
+                    }
+
+                    header.insert(String::from("Accept-Ranges"), String::from("bytes"));
+
+                    identity_len
+                }
+            }
+        }
+        Some(_) | None => {
+            if compression::is_compressible(&content_type) {
+                header.insert(String::from("Vary"), String::from("Accept-Encoding"));
+            }
+
+            header.insert(String::from("Accept-Ranges"), String::from("bytes"));
+
+            identity_len
+        }
+    };
+
+    header.insert(String::from("Content-Type"), content_type.to_string());
+    header.insert(String::from("Content-Length"), len.to_string());
+    header.insert(String::from("ETag"), etag);
+
+    if let Some(modified) = modified {
+        header.insert(String::from("Last-Modified"), http_date::format(modified));
+    }
+
+    Some(Response {
+        status: ResponseStatusCode::Ok,
+        header,
+        body: None,
+    })
+}
+
+/// Length, modification time & content hash of a file
+type FileMetadata = (u64, Option<SystemTime>, String);
+
+/// [`FileMetadata`] of a file, which is hashed unless its digest is cached
+fn file_metadata(file: PathBuf, utility_thread: &UtilitySender) -> Option<FileMetadata> {
+    match file_stat(file, true, utility_thread)? {
+        (len, modified, Some(hash)) => Some((len, modified, hash)),
+        _ => None,
+    }
+}
+
+/// Length & modification time of a file, along with its content hash if `digest`
+/// is set or the hash is already known
+fn file_stat(
+    file: PathBuf,
+    digest: bool,
+    utility_thread: &UtilitySender,
+) -> Option<(u64, Option<SystemTime>, Option<String>)> {
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((UtilityCommand::GetMetadata { file, digest }, tx));
+
+    match rx.recv() {
+        Ok(Ok(UtilityData::Metadata {
+            len,
+            modified,
+            hash,
+        })) => Some((len, modified, hash)),
+        _ => None,
+    }
+}
+
+/// Answers a `Range` request for a static file with `206` or `416`.
+///
+/// `None` if the full file should be sent instead: no (valid) `Range`, a stale
+/// `If-Range`, or a response that will be compressed on the fly.
+///
+/// The file is never hashed here, as that would read all of it. The ETag is only sent
+/// (& `If-Range` tags only match) once a full response has hashed it.
+/// Disjoint ranges are sent as `multipart/byteranges`.
+fn get_range(
+    heading: &HashMap<String, String>,
+    file: &Path,
+    utility_thread: &UtilitySender,
+) -> Option<Response> {
+    let range = heading.get("range")?;
+
+    let content_type = ContentType::try_from(file.extension()?.to_str()?).ok()?;
+
+    let (len, modified, hash) = file_stat(file.to_path_buf(), false, utility_thread)?;
+
+    // ranges refer to the identity content, so only serve them when it is sent as is
+    if compression::choose_for(
+        heading,
+        &content_type,
+        len as usize,
+        &Setting::get().compression,
+    )
+    .is_some()
+    {
+        return None;
+    }
+
+    // the identity content's tag, which encoded responses suffix with their coding
+    let etag = hash.map(|hash| conditional::etag(&hash));
+
+    if let Some(if_range) = heading.get("if-range") {
+        if !range::if_range_matches(if_range, etag.as_deref(), modified) {
+            return None;
+        }
+    }
+
+    let mut header = HashMap::from([(String::from("Accept-Ranges"), String::from("bytes"))]);
+
+    if let Some(etag) = etag {
+        header.insert(String::from("ETag"), etag);
+    }
+
+    if let Some(modified) = modified {
+        header.insert(String::from("Last-Modified"), http_date::format(modified));
+    }
+
+    let ranges = match range::parse(range, len) {
+        Ranges::Ignore => return None,
+        Ranges::Unsatisfiable => {
+            header.insert(String::from("Content-Range"), format!("bytes */{len}"));
+
+            return Some(Response {
+                status: ResponseStatusCode::RangeNotSatisfiable,
+                header,
+                body: None,
+            });
+        }
+        Ranges::Satisfiable(ranges) => ranges,
+    };
+
+    trace!("range: {file:?} {ranges:?}");
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::GetFileRanges {
+            file: file.to_path_buf(),
+            ranges: ranges.clone(),
+        },
+        tx,
+    ));
+
+    let Ok(Ok(UtilityData::Ranges(mut parts))) = rx.recv() else {
+        return None;
+    };
+
+    let content = match ranges.as_slice() {
+        [range] => {
+            header.insert(
+                String::from("Content-Range"),
+                range::content_range(*range, len),
+            );
+
+            parts.pop()?
+        }
+        _ => {
+            // unique to the response, so it can't turn up in the parts by chance
+            let boundary = format!(
+                "byteranges_{}",
+                hash_content(format!("{file:?} {len} {:?}", SystemTime::now()).as_bytes())
+            );
+
+            // overrides the type written for the body, which can't carry a boundary
+            header.insert(
+                String::from("Content-Type"),
+                format!("multipart/byteranges; boundary={boundary}"),
+            );
+
+            range::multipart(
+                &boundary,
+                &content_type.to_string(),
+                len,
+                ranges.into_iter().zip(parts).collect(),
+            )
+        }
+    };
+
+    Some(Response {
+        status: ResponseStatusCode::PartialContent,
+        header,
+        body: Some(Body {
+            content_type,
+            content,
+        }),
+    })
+}
+
+/// Moves the body's type & length into the headers & drops the body
+fn without_body(mut response: Response) -> Response {
+    if let Some(body) = response.body.take() {
+        response
+            .header
+            .insert(String::from("Content-Type"), body.content_type.to_string());
+        response
+            .header
+            .insert(String::from("Content-Length"), body.content.len().to_string());
+    }
+
+    response
+}
+
+/// Maps the requested file onto the host's path & checks its extension is allowed
+fn resolve_file<'a>(
+    file: &str,
+    heading: &HashMap<String, String>,
+    setting: &'a ServerSetting,
+) -> Result<(&'a String, PathBuf), ResponseStatusCode> {
+    let Some(host) = heading.get("host") else {
+        return Err(ResponseStatusCode::ImATeapot);
+    };
+
+    let Some((
+        host_path,
+        allowed_extension
+    )) = setting.paths.get(host).map(|host_path| (&host_path.path, &host_path.allow)) else {
+        return Err(ResponseStatusCode::NotFound);
+    };
+
+    trace!("{host_path} := {allowed_extension:#?}");
+
+    let file: PathBuf = {
+        let mut file_path = PathBuf::from(&host_path);
+        let file = file.trim_matches('/').replace("\\","/");
+        file_path.push(&file);
+
+        if file_path.extension().is_none() {
+            file_path.push("index.html");
+        }
+
+        let ext = file_path.extension().unwrap().to_str().unwrap();
+
+        if !allowed_extension.iter().any(|allowed| allowed == ext) {
+            return Err(ResponseStatusCode::Forbidden);
+        }
+
+        file_path.to_string_lossy().replace("\\", "/").into()
+    };
+
+    Ok((host_path, file))
+}
+
+/// Looks for `file.ext.br`/`file.ext.gz` matching the request's `Accept-Encoding`
+fn get_sidecar(
+    heading: &HashMap<String, String>,
+    file: &Path,
+    utility_thread: &UtilitySender,
+) -> Option<Response> {
+    let accept_encoding = heading.get("accept-encoding")?;
+
+    let content_type = ContentType::try_from(file.extension()?.to_str()?).ok()?;
+    if !compression::is_compressible(&content_type) {
+        return None;
+    }
+
+    let (_, modified, _) = file_metadata(file.to_path_buf(), utility_thread)?;
+
+    let (encoding, sidecar, (_, _, hash)) = fresh_sidecar(accept_encoding, file, modified, utility_thread)?;
+
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::GetFile {
+            file: sidecar,
+            bytes: true,
+        },
+        tx,
+    ));
+
+    let Ok(Ok(UtilityData::Bytes(content))) = rx.recv() else {
+        return None;
+    };
+
+    trace!("sidecar: {file:?}.{}", encoding.token());
+
+    let mut response = Response {
+        status: ResponseStatusCode::Ok,
+        header: HashMap::from([
+            (
+                String::from("Content-Encoding"),
+                String::from(encoding.token()),
+            ),
+            (String::from("Vary"), String::from("Accept-Encoding")),
+            (String::from("ETag"), conditional::etag(&hash)),
+        ]),
+        body: Some(Body {
+            content_type,
+            content: content.to_vec(),
+        }),
+    };
+
+    if let Some(modified) = modified {
+        conditional::add_last_modified(&mut response, modified);
+    }
+
+    Some(response)
+}
+
+/// Best sidecar of the file the client accepts, with its path & metadata.
+///
+/// Sidecars older than the file are skipped, so edits are served (compressed on the fly)
+/// before `precompress` is rerun.
+fn fresh_sidecar(
+    accept_encoding: &str,
+    file: &Path,
+    modified: Option<SystemTime>,
+    utility_thread: &UtilitySender,
+) -> Option<(Encoding, PathBuf, FileMetadata)> {
+    let mut available = Encoding::SIDECARS.to_vec();
+
+    while let Some(encoding) = compression::negotiate_among(accept_encoding, &available) {
+        available.retain(|sidecar| *sidecar != encoding);
+
+        let sidecar = encoding.sidecar(file)?;
+
+        let Some(metadata) = file_metadata(sidecar.clone(), utility_thread) else {
+            continue;
+        };
+
+        match (metadata.1, modified) {
+            (Some(sidecar_modified), Some(modified)) if sidecar_modified >= modified => {
+                return Some((encoding, sidecar, metadata));
+            }
+            _ => trace!("{sidecar:?} is stale"),
+        }
     }
 
-    Err(ResponseStatusCode::NotFound)
+    None
 }
 
 fn calculate_colour(foreground: f64, background: f64, opacity: f64) -> f64 {
@@ -983,7 +2009,7 @@ ActionBuilder!(
     name = action_boi,
     utility = UtilitySender,
     get = get,
-    head = not_allowed_logic,
+    head = head,
     post = post,
     put = not_allowed_logic,
     delete = not_allowed_logic,
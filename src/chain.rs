@@ -0,0 +1,281 @@
+use petgraph::{graph::NodeIndex, Directed, Direction::Outgoing, Graph};
+
+use std::collections::HashMap;
+
+use log::{debug, trace, warn};
+
+use crate::model::{Date, DevLog, Project, Relation};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    /// On the current path
+    Open,
+    Done,
+}
+
+/// Dev logs reachable from the project through `relate_tags`, oldest first.
+/// `dev_logs` holds every dev log by id, so it is built once for all projects.
+///
+/// The dev log linked to the project holding its summary starts the chain but isn't part of it.
+/// Branches are merged by `created` date; entries without a date keep the
+/// date of the entry found before them. Cycles are logged as malformed chains,
+/// and edges to other tags (skills, projects, ...) are ignored.
+pub fn dev_log_chain(
+    project: &Project,
+    relations: &[Relation],
+    dev_logs: &HashMap<i32, DevLog>,
+) -> Vec<DevLog> {
+    let summary = project.summary_id;
+    let project = project.id;
+
+    let mut graph: Graph<i32, (), Directed> = Graph::new();
+    let mut nodes: HashMap<i32, NodeIndex> = HashMap::new();
+
+    let mut node = |graph: &mut Graph<i32, (), Directed>, id: i32| {
+        *nodes.entry(id).or_insert_with(|| graph.add_node(id))
+    };
+
+    let start = node(&mut graph, project);
+
+    for relation in relations {
+        let (tag_1, tag_2) = (
+            node(&mut graph, relation.tag_1),
+            node(&mut graph, relation.tag_2),
+        );
+        graph.update_edge(tag_1, tag_2, ());
+    }
+
+    // iterative dfs, keeping the order entries are found in
+    let mut visits: HashMap<NodeIndex, Visit> = HashMap::from([(start, Visit::Open)]);
+    let mut stack = vec![(start, next_dev_logs(&graph, start, dev_logs))];
+    let mut found: Vec<i32> = Vec::new();
+
+    let is_summary = |node: NodeIndex, child: NodeIndex| node == start && graph[child] == summary;
+
+    while let Some((node, children)) = stack.last_mut() {
+        let node = *node;
+
+        let Some(child) = children.pop() else {
+            visits.insert(node, Visit::Done);
+            stack.pop();
+            continue;
+        };
+
+        match visits.get(&child) {
+            Some(Visit::Open) => {
+                warn!(
+                    "Dev log chain of project {project} has a cycle: {} -> {}",
+                    graph[node], graph[child]
+                );
+                continue;
+            }
+            Some(Visit::Done) => continue,
+            None => {}
+        }
+
+        visits.insert(child, Visit::Open);
+        if !is_summary(node, child) {
+            found.push(graph[child]);
+        }
+
+        let grand_children = next_dev_logs(&graph, child, dev_logs);
+        stack.push((child, grand_children));
+    }
+
+    trace!("project {project} chain: {found:?}");
+
+    let mut last_date: Option<Date> = None;
+    let mut chain: Vec<(Option<Date>, usize, DevLog)> = found
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, id)| {
+            let dev_log = dev_logs.get(&id)?.clone();
+            last_date = dev_log.created.or(last_date);
+
+            Some((last_date, index, dev_log))
+        })
+        .collect();
+
+    chain.sort_by_key(|(date, index, _)| (*date, *index));
+
+    chain.into_iter().map(|(_, _, dev_log)| dev_log).collect()
+}
+
+/// Dev logs directly after the node, reversed so they are popped in id order
+fn next_dev_logs(
+    graph: &Graph<i32, (), Directed>,
+    node: NodeIndex,
+    dev_logs: &HashMap<i32, DevLog>,
+) -> Vec<NodeIndex> {
+    let mut next: Vec<NodeIndex> = graph
+        .neighbors_directed(node, Outgoing)
+        .filter(|next| dev_logs.contains_key(&graph[*next]))
+        .collect();
+
+    // branches are allowed, so they are only noted
+    if next.len() > 1 {
+        debug!(
+            "Dev log chain branches after {}: {:?}",
+            graph[node],
+            next.iter().map(|next| graph[*next]).collect::<Vec<i32>>()
+        );
+    }
+
+    next.sort_by_key(|next| std::cmp::Reverse(graph[*next]));
+
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: i32 = 1;
+    const SUMMARY: i32 = 2;
+
+    fn project() -> Project {
+        Project {
+            id: PROJECT,
+            colour: String::from("ffffff"),
+            name: String::from("project"),
+            summary: String::from("summary"),
+            summary_id: SUMMARY,
+            repo: String::new(),
+            first_push: date(1),
+            last_push: None,
+        }
+    }
+
+    fn date(day: u8) -> Date {
+        Date {
+            year: 2023,
+            month: 1,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+
+    fn dev_log(id: i32, created: Option<u8>) -> DevLog {
+        DevLog {
+            id,
+            name: format!("log {id}"),
+            created: created.map(date),
+            body: format!("body {id}"),
+        }
+    }
+
+    fn relations(edges: &[(i32, i32)]) -> Vec<Relation> {
+        edges
+            .iter()
+            .map(|&(tag_1, tag_2)| Relation { tag_1, tag_2 })
+            .collect()
+    }
+
+    fn chain(edges: &[(i32, i32)], dev_logs: Vec<DevLog>) -> Vec<i32> {
+        let dev_logs: HashMap<i32, DevLog> = dev_logs
+            .into_iter()
+            .map(|dev_log| (dev_log.id, dev_log))
+            .collect();
+
+        dev_log_chain(&project(), &relations(edges), &dev_logs)
+            .into_iter()
+            .map(|dev_log| dev_log.id)
+            .collect()
+    }
+
+    #[test]
+    fn follows_the_chain() {
+        let dev_logs = vec![
+            dev_log(SUMMARY, Some(1)),
+            dev_log(10, Some(2)),
+            dev_log(11, None),
+            dev_log(12, Some(4)),
+        ];
+
+        assert_eq!(
+            chain(
+                &[(PROJECT, SUMMARY), (SUMMARY, 10), (10, 11), (11, 12)],
+                dev_logs
+            ),
+            vec![10, 11, 12]
+        );
+    }
+
+    #[test]
+    fn stops_at_cycles() {
+        let dev_logs = vec![
+            dev_log(SUMMARY, Some(1)),
+            dev_log(10, Some(2)),
+            dev_log(11, Some(3)),
+        ];
+
+        assert_eq!(
+            chain(
+                &[(PROJECT, SUMMARY), (SUMMARY, 10), (10, 11), (11, 10)],
+                dev_logs
+            ),
+            vec![10, 11]
+        );
+    }
+
+    #[test]
+    fn merges_forks_by_date() {
+        let dev_logs = vec![
+            dev_log(SUMMARY, Some(1)),
+            dev_log(10, Some(2)),
+            dev_log(11, Some(5)),
+            dev_log(12, Some(3)),
+            dev_log(13, Some(4)),
+        ];
+
+        // 10 forks into 11 & 12 -> 13
+        assert_eq!(
+            chain(
+                &[
+                    (PROJECT, SUMMARY),
+                    (SUMMARY, 10),
+                    (10, 11),
+                    (10, 12),
+                    (12, 13)
+                ],
+                dev_logs
+            ),
+            vec![10, 12, 13, 11]
+        );
+    }
+
+    #[test]
+    fn leaves_out_orphans_and_other_tags() {
+        let dev_logs = vec![
+            dev_log(SUMMARY, Some(1)),
+            dev_log(10, Some(2)),
+            dev_log(11, Some(3)),
+            dev_log(20, Some(1)),
+        ];
+
+        // 20 isn't linked & 30 is a skill
+        assert_eq!(
+            chain(
+                &[(PROJECT, SUMMARY), (SUMMARY, 10), (10, 30), (20, 11)],
+                dev_logs
+            ),
+            vec![10]
+        );
+    }
+
+    #[test]
+    fn keeps_dev_logs_matching_the_summary() {
+        let mut copy = dev_log(10, Some(2));
+        copy.body = project().summary;
+
+        let mut summary = dev_log(SUMMARY, Some(1));
+        summary.body = project().summary;
+
+        assert_eq!(
+            chain(&[(PROJECT, SUMMARY), (SUMMARY, 10)], vec![summary, copy]),
+            vec![10]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use pipelined_server::{http::body::ContentType, setting::ServerSetting};
+
+use serde_json::json;
+
+use std::{
+    env,
+    io::{Read, Write},
+    net::TcpStream,
+    path::Path,
+    time::Duration,
+};
+
+use log::{error, info};
+
+use crate::{
+    action::ADMIN_TOKEN,
+    compression::{self, write_sidecars},
+    markdown,
+    setting::{CompressionSetting, Setting},
+    utility::file::find_files,
+};
+
+const USAGE: &str =
+    "usage: server [precompress [host] | invalidate [host] | highlight-css [theme]]";
+
+/// Theme of `highlight-css` when none is given
+const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Runs a server subcommand, e.g. `server precompress 127.0.0.1`
+pub fn run(args: &[String]) -> Result<(), ()> {
+    match args.first().map(String::as_str) {
+        Some("precompress") => precompress(args.get(1).map(String::as_str)),
+        Some("invalidate") => invalidate(args.get(1).map(String::as_str)),
+        Some("highlight-css") => highlight_css(args.get(1).map(String::as_str)),
+        Some(command) => {
+            error!("Unknown command {command:?}. {USAGE}");
+            Err(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Writes `.br` & `.gz` sidecars for every compressible, allowed file under the host paths
+fn precompress(host: Option<&str>) -> Result<(), ()> {
+    let setting = ServerSetting::load();
+
+    // sidecars are generated once, so use the best compression available
+    let compression = CompressionSetting {
+        gzip_level: 9,
+        brotli_quality: 11,
+        ..Setting::get().compression.clone()
+    };
+
+    let mut hosts = setting
+        .paths
+        .iter()
+        .filter(|(name, _)| host.is_none_or(|host| host == name.as_str()))
+        .peekable();
+
+    if hosts.peek().is_none() {
+        error!("No host path for {host:?}");
+        return Err(());
+    }
+
+    let mut failed = false;
+
+    for (name, host_path) in hosts {
+        let mut files = Vec::new();
+        find_files(Path::new(&host_path.path), &mut files);
+
+        let mut written = 0;
+
+        for file in files {
+            let Some(ext) = file.extension().and_then(|ext| ext.to_str()) else {
+                continue;
+            };
+
+            if !host_path.allow.iter().any(|allowed| allowed == ext) {
+                continue;
+            }
+
+            let Ok(content_type) = ContentType::try_from(ext) else {
+                continue;
+            };
+
+            if !compression::is_compressible(&content_type) {
+                continue;
+            }
+
+            match write_sidecars(&file, &compression) {
+                Ok(count) => written += count,
+                Err(()) => failed = true,
+            }
+        }
+
+        info!("{name}: wrote {written} sidecars");
+    }
+
+    if failed {
+        return Err(());
+    }
+
+    Ok(())
+}
+
+/// Asks the running server to drop its cached pages
+fn invalidate(host: Option<&str>) -> Result<(), ()> {
+    let setting = ServerSetting::load();
+
+    let Ok(token) = env::var(ADMIN_TOKEN) else {
+        error!("{ADMIN_TOKEN} is not set");
+        return Err(());
+    };
+
+    let host = host
+        .map(String::from)
+        .or_else(|| setting.paths.keys().next().cloned())
+        .unwrap_or_else(|| setting.address.clone());
+
+    let body = json!({ "token": token }).to_string();
+    let request = format!(
+        "POST /invalidate_pages HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    let address = format!("{}:{}", setting.address, setting.port);
+
+    let response = TcpStream::connect(&address).and_then(|mut stream| {
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        Ok(response)
+    });
+
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            error!("Failed to reach {address}: {err}");
+            return Err(());
+        }
+    };
+
+    let status = response.lines().next().unwrap_or_default();
+
+    if status.split(' ').nth(1) != Some("200") {
+        error!("Invalidation refused: {status}");
+        return Err(());
+    }
+
+    info!("Invalidated cached pages on {host}");
+
+    Ok(())
+}
+
+/// Prints a stylesheet for the classes of highlighted code blocks
+fn highlight_css(theme: Option<&str>) -> Result<(), ()> {
+    let css = markdown::highlight_css(theme.unwrap_or(DEFAULT_THEME))?;
+
+    print!("{css}");
+
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use pipelined_server::http::{
+    body::{Application, ContentType, Image},
+    response::{response_status_code::ResponseStatusCode, Response},
+};
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use log::{error, info, trace};
+
+use crate::setting::CompressionSetting;
+
+/// Content codings supported by the server, in order of preference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    const PREFERENCE: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+    /// Codings that can be served from precompressed sidecar files
+    pub const SIDECARS: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+    /// Token used in `Accept-Encoding` & `Content-Encoding`
+    pub fn token(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Extension of a precompressed sidecar file (`file.ext.br`, `file.ext.gz`)
+    pub fn sidecar_extension(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Brotli => Some("br"),
+            Encoding::Gzip => Some("gz"),
+            Encoding::Deflate => None,
+        }
+    }
+
+    /// Path of the precompressed sidecar next to `file`
+    pub fn sidecar(&self, file: &Path) -> Option<PathBuf> {
+        let ext = self.sidecar_extension()?;
+
+        let mut sidecar = file.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(ext);
+
+        Some(PathBuf::from(sidecar))
+    }
+
+    pub fn encode(&self, content: &[u8], setting: &CompressionSetting) -> Result<Vec<u8>, ()> {
+        let result = match self {
+            Encoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(
+                    Vec::new(),
+                    4096,
+                    setting.brotli_quality.min(11),
+                    22,
+                );
+
+                encoder.write_all(content).map(|_| encoder.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder =
+                    GzEncoder::new(Vec::new(), Compression::new(setting.gzip_level.min(9)));
+
+                encoder.write_all(content).and_then(|_| encoder.finish())
+            }
+            // HTTP's "deflate" is the zlib format (RFC 9110 8.4.1.2)
+            Encoding::Deflate => {
+                let mut encoder =
+                    ZlibEncoder::new(Vec::new(), Compression::new(setting.gzip_level.min(9)));
+
+                encoder.write_all(content).and_then(|_| encoder.finish())
+            }
+        };
+
+        result.map_err(|err| error!("Failed to {} encode: {err}", self.token()))
+    }
+}
+
+/// Picks the best supported coding from an `Accept-Encoding` value.
+///
+/// The highest q-value wins; ties are broken by [`Encoding::PREFERENCE`].
+/// Returns `None` if identity should be used.
+pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    negotiate_among(accept_encoding, &Encoding::PREFERENCE)
+}
+
+/// [`negotiate`] limited to the given codings, which must be in order of preference
+pub fn negotiate_among(accept_encoding: &str, available: &[Encoding]) -> Option<Encoding> {
+    let mut accepted: HashMap<String, f32> = HashMap::new();
+
+    for coding in accept_encoding.split(',') {
+        let mut params = coding.split(';').map(str::trim);
+
+        let Some(name) = params.next().filter(|name| !name.is_empty()) else {
+            continue;
+        };
+
+        let q = params
+            .filter_map(|param| param.strip_prefix("q=").or(param.strip_prefix("Q=")))
+            .filter_map(|q| q.trim().parse::<f32>().ok())
+            .next()
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0);
+
+        accepted.insert(name.to_ascii_lowercase(), q);
+    }
+
+    let wildcard = accepted.get("*").copied();
+
+    available
+        .iter()
+        .filter_map(|encoding| {
+            let q = accepted.get(encoding.token()).copied().or(wildcard)?;
+
+            (q > 0.0).then_some((*encoding, q))
+        })
+        .fold(
+            None,
+            |best: Option<(Encoding, f32)>, (encoding, q)| match best {
+                Some((_, best_q)) if best_q >= q => best,
+                _ => Some((encoding, q)),
+            },
+        )
+        .map(|(encoding, _)| encoding)
+}
+
+/// Checks if compressing the content is worth it.
+/// Images (other than svg & ico), video & fonts are already compressed.
+pub fn is_compressible(content_type: &ContentType) -> bool {
+    matches!(
+        content_type,
+        ContentType::Text(_)
+            | ContentType::Application(Application::json)
+            | ContentType::Image(Image::svg_xml)
+            | ContentType::Image(Image::x_icon)
+    )
+}
+
+/// Coding [`compress`] will apply to the response, if any
+pub fn choose(
+    heading: &HashMap<String, String>,
+    response: &Response,
+    setting: &CompressionSetting,
+) -> Option<Encoding> {
+    let body = response.body.as_ref()?;
+
+    // partial content is cut from the identity content
+    if response.header.contains_key("Content-Encoding")
+        || matches!(response.status, ResponseStatusCode::PartialContent)
+    {
+        return None;
+    }
+
+    choose_for(heading, &body.content_type, body.content.len(), setting)
+}
+
+/// Coding [`compress`] would apply to a body of this type & length, if any
+pub fn choose_for(
+    heading: &HashMap<String, String>,
+    content_type: &ContentType,
+    len: usize,
+    setting: &CompressionSetting,
+) -> Option<Encoding> {
+    if !is_compressible(content_type) || len < setting.min_size {
+        return None;
+    }
+
+    negotiate(heading.get("accept-encoding")?)
+}
+
+/// Marks a compressible response as depending on `Accept-Encoding`,
+/// even when it is sent uncompressed
+pub fn add_vary(response: &mut Response) {
+    let Some(body) = &response.body else {
+        return;
+    };
+
+    if is_compressible(&body.content_type) {
+        response
+            .header
+            .insert(String::from("Vary"), String::from("Accept-Encoding"));
+    }
+}
+
+/// Compresses the response body with the coding negotiated from the request heading
+pub fn compress(
+    heading: &HashMap<String, String>,
+    mut response: Response,
+    setting: &CompressionSetting,
+) -> Response {
+    add_vary(&mut response);
+
+    let Some(encoding) = choose(heading, &response, setting) else {
+        return response;
+    };
+
+    let Some(body) = &mut response.body else {
+        return response;
+    };
+
+    match encoding.encode(&body.content, setting) {
+        Ok(content) => {
+            trace!(
+                "{}: {} -> {} bytes",
+                encoding.token(),
+                body.content.len(),
+                content.len()
+            );
+
+            body.content = content;
+            response.header.insert(
+                String::from("Content-Encoding"),
+                String::from(encoding.token()),
+            );
+        }
+        Err(()) => {
+            // the ETag was made for the encoded body
+            response.header.remove("ETag");
+        }
+    }
+
+    response
+}
+
+/// Writes `file.ext.br` & `file.ext.gz` next to `file` unless they are newer than it.
+/// Returns the number of sidecars written.
+pub fn write_sidecars(file: &Path, setting: &CompressionSetting) -> Result<usize, ()> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+
+    let Some(source_modified) = modified(file) else {
+        error!("Failed to stat {file:?}");
+        return Err(());
+    };
+
+    let content = match fs::read(file) {
+        Ok(content) => content,
+        Err(err) => {
+            error!("Failed to read {file:?}: {err}");
+            return Err(());
+        }
+    };
+
+    let mut written = 0;
+
+    for encoding in Encoding::SIDECARS {
+        let Some(sidecar) = encoding.sidecar(file) else {
+            continue;
+        };
+
+        if modified(&sidecar).is_some_and(|sidecar_modified| sidecar_modified >= source_modified) {
+            trace!("{sidecar:?} is up to date");
+            continue;
+        }
+
+        let encoded = encoding.encode(&content, setting)?;
+
+        if encoded.len() >= content.len() {
+            trace!("{sidecar:?} would not be smaller; skipped");
+            continue;
+        }
+
+        if let Err(err) = fs::write(&sidecar, encoded) {
+            error!("Failed to write {sidecar:?}: {err}");
+            return Err(());
+        }
+
+        info!("Wrote {sidecar:?}");
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pipelined_server::http::body::{Body, Text};
+
+    fn response(content_type: ContentType, len: usize) -> Response {
+        Response {
+            status: ResponseStatusCode::Ok,
+            header: HashMap::new(),
+            body: Some(Body {
+                content_type,
+                content: vec![b'a'; len],
+            }),
+        }
+    }
+
+    fn heading(accept_encoding: &str) -> HashMap<String, String> {
+        HashMap::from([(
+            String::from("accept-encoding"),
+            String::from(accept_encoding),
+        )])
+    }
+
+    #[test]
+    fn negotiates_by_preference() {
+        assert_eq!(negotiate("gzip, deflate, br"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("deflate, gzip"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("identity"), None);
+        assert_eq!(negotiate(""), None);
+    }
+
+    #[test]
+    fn negotiates_by_q_value() {
+        assert_eq!(negotiate("br;q=0.5, gzip;q=0.8"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("br;q=0.5, GZIP;Q=0.8"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("gzip;q=1, br;q=1"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("br;q=0, gzip;q=0.1"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("br;q=0"), None);
+        assert_eq!(negotiate("br;q=oops"), Some(Encoding::Brotli));
+    }
+
+    #[test]
+    fn negotiates_wildcards() {
+        assert_eq!(negotiate("*"), Some(Encoding::Brotli));
+        assert_eq!(negotiate("*;q=0.5, br;q=0"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("*;q=0"), None);
+        assert_eq!(
+            negotiate_among("*", &Encoding::SIDECARS[1..]),
+            Some(Encoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn skips_small_and_compressed_bodies() {
+        let setting = CompressionSetting::default();
+        let heading = heading("gzip");
+
+        assert_eq!(
+            choose(
+                &heading,
+                &response(ContentType::Text(Text::html), 2048),
+                &setting
+            ),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            choose(
+                &heading,
+                &response(ContentType::Text(Text::html), 16),
+                &setting
+            ),
+            None
+        );
+        assert_eq!(
+            choose(
+                &heading,
+                &response(ContentType::Image(Image::png), 2048),
+                &setting
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn varies_compressible_responses() {
+        let setting = CompressionSetting::default();
+
+        let small = compress(
+            &heading("br"),
+            response(ContentType::Text(Text::css), 16),
+            &setting,
+        );
+        assert_eq!(
+            small.header.get("Vary").map(String::as_str),
+            Some("Accept-Encoding")
+        );
+        assert_eq!(small.header.get("Content-Encoding"), None);
+
+        let large = compress(
+            &heading("br"),
+            response(ContentType::Text(Text::css), 2048),
+            &setting,
+        );
+        assert_eq!(
+            large.header.get("Content-Encoding").map(String::as_str),
+            Some("br")
+        );
+        assert!(large.body.is_some_and(|body| body.content.len() < 2048));
+
+        let image = compress(
+            &heading("br"),
+            response(ContentType::Image(Image::png), 2048),
+            &setting,
+        );
+        assert_eq!(image.header.get("Vary"), None);
+    }
+}
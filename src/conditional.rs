@@ -0,0 +1,217 @@
+use pipelined_server::http::response::{response_status_code::ResponseStatusCode, Response};
+
+use std::{collections::HashMap, time::SystemTime};
+
+use crate::{compression::Encoding, http_date, utility::file::hash_content};
+
+/// Quotes a content hash as a strong entity tag
+pub fn etag(hash: &str) -> String {
+    format!("\"{hash}\"")
+}
+
+/// Tags the response with a strong ETag of its body, unless it already has one.
+/// The tag is suffixed with the content coding the body is about to be sent with,
+/// so each encoding of the body has its own validator.
+pub fn add_etag(response: &mut Response, encoding: Option<Encoding>) {
+    if let Some(etag) = response.header.get_mut("ETag") {
+        if let Some(encoding) = encoding {
+            *etag = encoded_etag(etag, encoding);
+        }
+        return;
+    }
+
+    let Some(body) = &response.body else {
+        return;
+    };
+
+    let hash = hash_content(&body.content);
+
+    let etag = match encoding {
+        Some(encoding) => etag(&format!("{hash}-{}", encoding.token())),
+        None => etag(&hash),
+    };
+
+    response.header.insert(String::from("ETag"), etag);
+}
+
+/// Entity tag of the identity content's tag once encoded, e.g. `"hash"` -> `"hash-br"`
+pub fn encoded_etag(etag: &str, encoding: Encoding) -> String {
+    let (weak, tag) = match etag.strip_prefix("W/") {
+        Some(tag) => ("W/", tag),
+        None => ("", etag),
+    };
+
+    format!("{weak}\"{}-{}\"", tag.trim_matches('"'), encoding.token())
+}
+
+pub fn add_last_modified(response: &mut Response, modified: SystemTime) {
+    response
+        .header
+        .insert(String::from("Last-Modified"), http_date::format(modified));
+}
+
+/// Evaluates `If-None-Match` & `If-Modified-Since` (RFC 9110 13.2.2).
+/// Range responses are included since the preconditions come before `Range`.
+pub fn is_not_modified(heading: &HashMap<String, String>, response: &Response) -> bool {
+    if !matches!(
+        response.status,
+        ResponseStatusCode::Ok
+            | ResponseStatusCode::PartialContent
+            | ResponseStatusCode::RangeNotSatisfiable
+    ) {
+        return false;
+    }
+
+    if let Some(if_none_match) = heading.get("if-none-match") {
+        let Some(etag) = response.header.get("ETag") else {
+            return false;
+        };
+
+        return if_none_match.trim() == "*"
+            || if_none_match
+                .split(',')
+                .map(|tag| tag.trim())
+                // weak comparison
+                .map(|tag| tag.strip_prefix("W/").unwrap_or(tag))
+                .any(|tag| tag == etag.strip_prefix("W/").unwrap_or(etag));
+    }
+
+    let (Some(if_modified_since), Some(last_modified)) = (
+        heading
+            .get("if-modified-since")
+            .and_then(|date| http_date::parse(date)),
+        response
+            .header
+            .get("Last-Modified")
+            .and_then(|date| http_date::parse(date)),
+    ) else {
+        return false;
+    };
+
+    last_modified <= if_modified_since
+}
+
+/// `304 Not Modified` keeping the validators & caching headers of the full response
+pub fn not_modified(response: Response) -> Response {
+    let header = response
+        .header
+        .into_iter()
+        .filter(|(key, _)| {
+            matches!(
+                key.as_str(),
+                "ETag"
+                    | "Last-Modified"
+                    | "Vary"
+                    | "Cache-Control"
+                    | "Expires"
+                    | "Content-Location"
+            )
+        })
+        .collect();
+
+    Response {
+        status: ResponseStatusCode::NotModified,
+        header,
+        body: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pipelined_server::http::body::{Body, ContentType, Text};
+
+    fn response() -> Response {
+        Response {
+            status: ResponseStatusCode::Ok,
+            header: HashMap::new(),
+            body: Some(Body {
+                content_type: ContentType::Text(Text::html),
+                content: b"<p>a</p>".to_vec(),
+            }),
+        }
+    }
+
+    fn tagged(encoding: Option<Encoding>) -> Response {
+        let mut response = response();
+        add_etag(&mut response, encoding);
+
+        response
+    }
+
+    fn if_none_match(etag: &str) -> HashMap<String, String> {
+        HashMap::from([(String::from("if-none-match"), String::from(etag))])
+    }
+
+    #[test]
+    fn tags_each_coding() {
+        let hash = hash_content(b"<p>a</p>");
+
+        assert_eq!(tagged(None).header["ETag"], etag(&hash));
+        assert_eq!(
+            tagged(Some(Encoding::Brotli)).header["ETag"],
+            format!("\"{hash}-br\"")
+        );
+        assert_eq!(
+            encoded_etag(&format!("W/\"{hash}\""), Encoding::Gzip),
+            format!("W/\"{hash}-gzip\"")
+        );
+    }
+
+    #[test]
+    fn matches_coding_tags() {
+        let brotli = tagged(Some(Encoding::Brotli));
+        let gzip = tagged(Some(Encoding::Gzip));
+        let identity = tagged(None);
+
+        let heading = if_none_match(&brotli.header["ETag"]);
+        assert!(is_not_modified(&heading, &brotli));
+        assert!(!is_not_modified(&heading, &gzip));
+        assert!(!is_not_modified(&heading, &identity));
+
+        let heading = if_none_match(&format!("\"other\", W/{}", gzip.header["ETag"]));
+        assert!(is_not_modified(&heading, &gzip));
+        assert!(!is_not_modified(&heading, &brotli));
+
+        assert!(is_not_modified(&if_none_match("*"), &identity));
+    }
+
+    #[test]
+    fn prefers_if_none_match() {
+        let mut response = tagged(None);
+        add_last_modified(&mut response, SystemTime::UNIX_EPOCH);
+
+        let mut heading = if_none_match("\"other\"");
+        heading.insert(
+            String::from("if-modified-since"),
+            http_date::format(SystemTime::now()),
+        );
+        assert!(!is_not_modified(&heading, &response));
+
+        heading.remove("if-none-match");
+        assert!(is_not_modified(&heading, &response));
+    }
+
+    #[test]
+    fn keeps_validators_on_304() {
+        let mut response = tagged(Some(Encoding::Gzip));
+        response
+            .header
+            .insert(String::from("Vary"), String::from("Accept-Encoding"));
+        response
+            .header
+            .insert(String::from("Content-Length"), String::from("8"));
+
+        let not_modified = not_modified(response);
+
+        assert!(matches!(
+            not_modified.status,
+            ResponseStatusCode::NotModified
+        ));
+        assert!(not_modified.body.is_none());
+        assert!(not_modified.header.contains_key("ETag"));
+        assert!(not_modified.header.contains_key("Vary"));
+        assert!(!not_modified.header.contains_key("Content-Length"));
+    }
+}
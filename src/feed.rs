@@ -0,0 +1,192 @@
+use serde_json::json;
+
+use std::time::SystemTime;
+
+use crate::{http_date, template::escape::html};
+
+/// Syndication format of a feed, picked by its file name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// RSS 2.0
+    Rss,
+    /// Atom (RFC 4287)
+    Atom,
+    /// JSON Feed 1.1
+    Json,
+}
+
+/// Matches `feed.xml`, `feed.atom` & `feed.json` at the site root and `{project}/feed.xml`.
+/// Returns the format & project slug of the feed.
+pub fn route(file: &str) -> Option<(Format, Option<&str>)> {
+    let file = file.trim_matches('/');
+
+    match file.split('/').collect::<Vec<&str>>().as_slice() {
+        ["feed.xml"] => Some((Format::Rss, None)),
+        ["feed.atom"] => Some((Format::Atom, None)),
+        ["feed.json"] => Some((Format::Json, None)),
+        [project, "feed.xml"] if !project.is_empty() => Some((Format::Rss, Some(project))),
+        _ => None,
+    }
+}
+
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    /// Page the feed belongs to
+    pub link: String,
+    /// Url of the feed itself
+    pub url: String,
+    pub updated: Option<SystemTime>,
+    /// Newest first
+    pub entries: Vec<Entry>,
+}
+
+pub struct Entry {
+    /// Stable id, kept when the title (and so the permalink) changes
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub published: Option<SystemTime>,
+    /// Name of the project the entry belongs to
+    pub category: String,
+    /// Rendered html
+    pub content: String,
+}
+
+impl Feed {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Rss => self.rss(),
+            Format::Atom => self.atom(),
+            Format::Json => self.json(),
+        }
+    }
+
+    fn rss(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n",
+        );
+
+        xml.push_str(&format!(
+            "<title>{}</title>\n<link>{}</link>\n<description>{}</description>\n<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+            html(&self.title),
+            html(&self.link),
+            html(&self.description),
+            html(&self.url)
+        ));
+
+        if let Some(updated) = self.updated {
+            xml.push_str(&format!(
+                "<lastBuildDate>{}</lastBuildDate>\n",
+                http_date::format(updated)
+            ));
+        }
+
+        for entry in &self.entries {
+            xml.push_str(&format!(
+                "<item>\n<title>{}</title>\n<link>{}</link>\n<guid isPermaLink=\"false\">{}</guid>\n<category>{}</category>\n",
+                html(&entry.title),
+                html(&entry.link),
+                html(&entry.id),
+                html(&entry.category)
+            ));
+
+            if let Some(published) = entry.published {
+                xml.push_str(&format!(
+                    "<pubDate>{}</pubDate>\n",
+                    http_date::format(published)
+                ));
+            }
+
+            xml.push_str(&format!(
+                "<description>{}</description>\n</item>\n",
+                html(&entry.content)
+            ));
+        }
+
+        xml.push_str("</channel>\n</rss>\n");
+
+        xml
+    }
+
+    fn atom(&self) -> String {
+        // atom requires an update time on the feed & every entry
+        let updated = self.updated.unwrap_or(SystemTime::UNIX_EPOCH);
+        // & an author on the feed or every entry
+        let author = match self.author.is_empty() {
+            true => &self.title,
+            false => &self.author,
+        };
+
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+        );
+
+        xml.push_str(&format!(
+            "<id>{}</id>\n<title>{}</title>\n<subtitle>{}</subtitle>\n<author><name>{}</name></author>\n<link href=\"{}\"/>\n<link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>\n<updated>{}</updated>\n",
+            html(&self.url),
+            html(&self.title),
+            html(&self.description),
+            html(author),
+            html(&self.link),
+            html(&self.url),
+            http_date::rfc3339(updated)
+        ));
+
+        for entry in &self.entries {
+            let published = http_date::rfc3339(entry.published.unwrap_or(updated));
+
+            xml.push_str(&format!(
+                "<entry>\n<id>{}</id>\n<title>{}</title>\n<link href=\"{}\"/>\n<published>{published}</published>\n<updated>{published}</updated>\n<category term=\"{}\"/>\n<content type=\"html\" xml:base=\"{}\">{}</content>\n</entry>\n",
+                html(&entry.id),
+                html(&entry.title),
+                html(&entry.link),
+                html(&entry.category),
+                html(&entry.link),
+                html(&entry.content)
+            ));
+        }
+
+        xml.push_str("</feed>\n");
+
+        xml
+    }
+
+    fn json(&self) -> String {
+        let items = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut item = json!({
+                    "id": entry.id,
+                    "url": entry.link,
+                    "title": entry.title,
+                    "content_html": entry.content,
+                    "tags": [entry.category],
+                });
+
+                if let Some(published) = entry.published {
+                    item["date_published"] = json!(http_date::rfc3339(published));
+                }
+
+                item
+            })
+            .collect::<Vec<serde_json::Value>>();
+
+        let mut feed = json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "description": self.description,
+            "home_page_url": self.link,
+            "feed_url": self.url,
+            "items": items,
+        });
+
+        if !self.author.is_empty() {
+            feed["authors"] = json!([{ "name": self.author }]);
+        }
+
+        feed.to_string()
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [`days_from_civil`]. Returns (year, month, day)
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Formats a time as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn format(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => 0,
+    };
+
+    let days = seconds.div_euclid(86400);
+    let second_of_day = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        DAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        second_of_day / 3600,
+        second_of_day % 3600 / 60,
+        second_of_day % 60
+    )
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, e.g. `1994-11-06T08:49:37Z`
+pub fn rfc3339(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => 0,
+    };
+
+    let second_of_day = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day % 3600 / 60,
+        second_of_day % 60
+    )
+}
+
+/// Parses an IMF-fixdate. Obsolete RFC 850 & asctime formats are not accepted.
+pub fn parse(date: &str) -> Option<SystemTime> {
+    // Sun, 06 Nov 1994 08:49:37 GMT
+    let (_day_name, date) = date.trim().split_once(", ")?;
+    let mut parts = date.split(' ');
+
+    let day = parts.next()?.parse::<u32>().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u32 + 1;
+    let year = parts.next()?.parse::<i64>().ok()?;
+
+    let mut time = parts.next()?.split(':').map(|part| part.parse::<u64>());
+    let hour = time.next()?.ok()?;
+    let minute = time.next()?.ok()?;
+    let second = time.next()?.ok()?;
+
+    if parts.next()? != "GMT" || day == 0 || day > 31 || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return None;
+    }
+
+    Some(UNIX_EPOCH + Duration::from_secs(days as u64 * 86400 + hour * 3600 + minute * 60 + second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sun, 06 Nov 1994 08:49:37 GMT
+    const EXAMPLE: u64 = 784111777;
+
+    #[test]
+    fn parses_imf_fixdate() {
+        assert_eq!(
+            parse("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(EXAMPLE))
+        );
+        assert_eq!(parse(" Thu, 01 Jan 1970 00:00:00 GMT "), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse("Thu, 29 Feb 2024 23:59:59 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1709251199))
+        );
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49 GMT"), None);
+        assert_eq!(parse("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun, 00 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse("Wed, 31 Dec 1969 23:59:59 GMT"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn formats_dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(EXAMPLE);
+
+        assert_eq!(format(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(rfc3339(time), "1994-11-06T08:49:37Z");
+        assert_eq!(format(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+    }
+
+    #[test]
+    fn round_trips() {
+        for seconds in [0, 59, 86399, 951782400, 1709251199, 4102444800] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+
+            assert_eq!(parse(&format(time)), Some(time));
+        }
+    }
+
+    #[test]
+    fn converts_civil_dates() {
+        for days in [-719468, -1, 0, 11016, 19782, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    }
+}
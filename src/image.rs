@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use log::{trace, warn};
+
+use crate::template::escape::{html, url_component, url_decode};
+
+/// Where the images of a dev log are linked from & looked up
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Location<'a> {
+    /// Url path relative sources resolve against, e.g. `/project/` for a dev log
+    pub page: &'a str,
+    pub host_path: Option<&'a Path>,
+    /// Prefix of root relative urls, e.g. `http://host`
+    pub base_url: Option<&'a str>,
+}
+
+/// Lazily loaded `<img>`. Images found under the host path get their intrinsic
+/// `width` & `height`, and a `srcset` when resized copies sit next to them.
+///
+/// Resized copies are named after the image & their width, e.g. `diagram-480w.png`
+/// for a 480px wide copy of `diagram.png`.
+///
+/// Relative sources are resolved against the url the dev log is served from & written
+/// root relative, so they load wherever the dev log is shown.
+pub fn img(src: &str, alt: &str, title: &str, location: &Location) -> String {
+    let resolved = match is_external(src) {
+        true => None,
+        false => {
+            let resolved = resolve(src, location.page);
+
+            if resolved.is_none() {
+                warn!("Broken image {src:?}: outside the host path");
+            }
+
+            resolved
+        }
+    };
+    let src = resolved.as_deref().unwrap_or(src);
+
+    let url = |path: &str| match location.base_url {
+        Some(base_url) if path.starts_with('/') && !path.starts_with("//") => {
+            format!("{base_url}{path}")
+        }
+        _ => String::from(path),
+    };
+
+    let mut img = format!("<img src=\"{}\" alt=\"{}\"", html(&url(src)), html(alt));
+
+    if !title.is_empty() {
+        img.push_str(&format!(" title=\"{}\"", html(title)));
+    }
+
+    let file = match (location.host_path, &resolved) {
+        (Some(host_path), Some(src)) => local_file(host_path, src),
+        _ => None,
+    };
+
+    if let Some(file) = file {
+        match imagesize::size(&file) {
+            Ok(size) => {
+                img.push_str(&format!(
+                    " width=\"{}\" height=\"{}\"",
+                    size.width, size.height
+                ));
+
+                let copies = resized_copies(&file);
+
+                if !copies.is_empty() {
+                    let path = src.split(['?', '#']).next().unwrap_or_default();
+                    let directory = &path[..path.rfind('/').map_or(0, |index| index + 1)];
+
+                    let srcset = copies
+                        .iter()
+                        .map(|(width, name)| {
+                            format!(
+                                "{} {width}w",
+                                url(&format!("{directory}{}", url_component(name)))
+                            )
+                        })
+                        .chain([format!("{} {}w", url(src), size.width)])
+                        .collect::<Vec<String>>()
+                        .join(", ");
+
+                    img.push_str(&format!(
+                        " srcset=\"{}\" sizes=\"(max-width: {width}px) 100vw, {width}px\"",
+                        html(&srcset),
+                        width = size.width
+                    ));
+                }
+            }
+            // e.g. svg, which has no intrinsic size in pixels
+            Err(err) => trace!("Unsized image {src:?}: {err}"),
+        }
+    }
+
+    img.push_str(" loading=\"lazy\" />");
+
+    img
+}
+
+/// Root relative url of the source, resolved against the page's url path like a browser
+/// would. `None` if it climbs above the root.
+fn resolve(src: &str, page: &str) -> Option<String> {
+    let (path, suffix) = src.split_at(src.find(['?', '#']).unwrap_or(src.len()));
+
+    let directory = match path.starts_with('/') {
+        true => "",
+        false => &page[..page.rfind('/').map_or(0, |index| index + 1)],
+    };
+
+    let joined = format!("{directory}/{path}");
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    Some(format!("/{}{suffix}", segments.join("/")))
+}
+
+/// File a root relative url refers to under the host path.
+/// `None`, with a warning, if there is no such file.
+fn local_file(host_path: &Path, src: &str) -> Option<PathBuf> {
+    let path = url_decode(src.split(['?', '#']).next().unwrap_or_default());
+    let path = Path::new(path.trim_start_matches('/'));
+
+    // decoded `%2E%2E`s can still climb out
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        warn!("Broken image {src:?}: outside the host path");
+        return None;
+    }
+
+    let file = host_path.join(path);
+
+    if !file.is_file() {
+        warn!("Broken image {src:?}: {} doesn't exist", file.display());
+        return None;
+    }
+
+    Some(file)
+}
+
+/// `//host/...` or a source with a scheme, e.g. `https:` or `data:`
+fn is_external(src: &str) -> bool {
+    src.starts_with("//")
+        || src.split_once(':').is_some_and(|(scheme, _)| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+}
+
+/// Width & file name of each resized copy of the image, narrowest first
+fn resized_copies(file: &Path) -> Vec<(usize, String)> {
+    let (Some(directory), Some(stem), Some(extension)) = (
+        file.parent(),
+        file.file_stem().and_then(|stem| stem.to_str()),
+        file.extension().and_then(|extension| extension.to_str()),
+    ) else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    let mut copies: Vec<(usize, String)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+
+            let width = name
+                .strip_prefix(stem)?
+                .strip_prefix('-')?
+                .strip_suffix(extension)?
+                .strip_suffix('.')?
+                .strip_suffix('w')?;
+
+            match width.starts_with(|c: char| c.is_ascii_digit()) {
+                true => Some((width.parse().ok()?, name)),
+                false => None,
+            }
+        })
+        .collect();
+
+    copies.sort();
+
+    copies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    /// Header of a png, which is all `imagesize` reads
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0, 0, 0, 0, 0]);
+
+        png
+    }
+
+    fn page(page: &str) -> Location<'_> {
+        Location {
+            page,
+            ..Location::default()
+        }
+    }
+
+    #[test]
+    fn resolves_against_the_page() {
+        assert_eq!(
+            resolve("a.png", "/project/").as_deref(),
+            Some("/project/a.png")
+        );
+        assert_eq!(
+            resolve("./img/../a.png?v=1#x", "/project/log_1").as_deref(),
+            Some("/project/a.png?v=1#x")
+        );
+        assert_eq!(resolve("../a.png", "/project/").as_deref(), Some("/a.png"));
+        assert_eq!(
+            resolve("/img/a.png", "/project/").as_deref(),
+            Some("/img/a.png")
+        );
+        assert_eq!(resolve("../../a.png", "/project/"), None);
+    }
+
+    #[test]
+    fn detects_external_sources() {
+        assert!(is_external("https://example.com/a.png"));
+        assert!(is_external("//example.com/a.png"));
+        assert!(is_external("data:image/png;base64,AA"));
+        assert!(!is_external("a.png"));
+        assert!(!is_external("/a.png"));
+        assert!(!is_external("img/a:b.png?t=1:2"));
+    }
+
+    #[test]
+    fn writes_lazy_imgs() {
+        assert_eq!(
+            img("a b.png", "a \"diagram\"", "", &page("/project/")),
+            "<img src=\"/project/a b.png\" alt=\"a &quot;diagram&quot;\" loading=\"lazy\" />"
+        );
+        assert_eq!(
+            img("https://a/b.png", "", "t", &page("/project/")),
+            "<img src=\"https://a/b.png\" alt=\"\" title=\"t\" loading=\"lazy\" />"
+        );
+        assert_eq!(
+            img("../../a.png", "", "", &page("/project/")),
+            "<img src=\"../../a.png\" alt=\"\" loading=\"lazy\" />"
+        );
+    }
+
+    #[test]
+    fn prefixes_the_base_url() {
+        let location = Location {
+            page: "/project/",
+            base_url: Some("http://host"),
+            ..Location::default()
+        };
+
+        assert_eq!(
+            img("a.png", "", "", &location),
+            "<img src=\"http://host/project/a.png\" alt=\"\" loading=\"lazy\" />"
+        );
+        assert_eq!(
+            img("//cdn/a.png", "", "", &location),
+            "<img src=\"//cdn/a.png\" alt=\"\" loading=\"lazy\" />"
+        );
+    }
+
+    #[test]
+    fn sizes_local_images() {
+        let dir = env::temp_dir().join(format!("image_sizes_{}", std::process::id()));
+        fs::create_dir_all(dir.join("project")).unwrap();
+        fs::write(dir.join("project/a.png"), png(960, 480)).unwrap();
+        fs::write(dir.join("project/a-480w.png"), png(480, 240)).unwrap();
+        fs::write(dir.join("project/a-240w.png"), png(240, 120)).unwrap();
+        fs::write(dir.join("project/a-big.png"), png(1, 1)).unwrap();
+        fs::write(dir.join("b.png"), png(10, 20)).unwrap();
+
+        let location = Location {
+            page: "/project/",
+            host_path: Some(&dir),
+            ..Location::default()
+        };
+
+        let sized = img("a.png", "", "", &location);
+        let missing = img("b.png", "", "", &location);
+        let outside = local_file(&dir, "/%2E%2E/b.png");
+        let root = img("/b.png", "", "", &location);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            sized,
+            "<img src=\"/project/a.png\" alt=\"\" width=\"960\" height=\"480\" \
+             srcset=\"/project/a-240w.png 240w, /project/a-480w.png 480w, /project/a.png 960w\" \
+             sizes=\"(max-width: 960px) 100vw, 960px\" loading=\"lazy\" />"
+        );
+        // missing files are linked unsized
+        assert_eq!(
+            missing,
+            "<img src=\"/project/b.png\" alt=\"\" loading=\"lazy\" />"
+        );
+        assert_eq!(outside, None);
+        assert_eq!(
+            root,
+            "<img src=\"/b.png\" alt=\"\" width=\"10\" height=\"20\" loading=\"lazy\" />"
+        );
+    }
+}
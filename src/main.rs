@@ -1,5 +1,7 @@
-use action::{action_boi, generate_utility_thread};
+use action::action_boi;
 use logging::logger_init;
+use setting::Setting;
+use utility::{generate_utility_thread, UtilityCommand};
 use pipelined_server::{
     pipeline::{
         builder::pipeline::Builder,
@@ -12,8 +14,26 @@ use pipelined_server::{
     setting::ServerSetting,
 };
 
+use std::{env, process, sync::mpsc};
+
 mod action;
+mod chain;
+mod cli;
+mod compression;
+mod conditional;
+mod feed;
+mod http_date;
+mod image;
 mod logging;
+mod markdown;
+mod model;
+mod range;
+mod sanitize;
+mod search;
+mod setting;
+mod sitemap;
+mod template;
+mod utility;
 //mod post_logic;
 //mod sql_reader;
 //mod get_logic;
@@ -21,7 +41,16 @@ mod logging;
 fn main() {
     logger_init();
 
-    let utility_thread = generate_utility_thread();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        if cli::run(&args).is_err() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let utility_thread = generate_utility_thread(&Setting::get().utility);
+    let utility_sender = utility_thread.0.clone();
 
     let setting = ServerSetting::load();
 
@@ -31,10 +60,16 @@ fn main() {
         .set_settings(setting.clone())
         .set_parser(default::parser::<264, 1024>)
         .set_action(action_boi)
+        // responses are compressed in `action` once the encoding is negotiated
         .set_compression(default::no_compression)
         .set_utility_thread(utility_thread.0.clone());
 
     let server = Server::new(setting, utility_thread, builder);
 
     server.run::<1>();
+
+    let (tx, rx) = mpsc::channel();
+    if utility_sender.send((UtilityCommand::Shutdown, tx)).is_ok() {
+        let _ = rx.recv();
+    }
 }
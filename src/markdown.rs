@@ -0,0 +1,728 @@
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Sum,
+    ops::AddAssign,
+    path::Path,
+    sync::OnceLock,
+};
+
+use log::error;
+
+use crate::{
+    image::{self, Location},
+    setting::MarkdownSetting,
+    template::escape::html as escape_html,
+};
+
+/// Words read per minute, for estimated reading times
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Classes of highlighted tokens are prefixed so they can't clash with the site's own
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Heading of rendered markdown, as listed in a table of contents
+#[derive(Clone, Debug)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// `None` when heading anchors are disabled
+    pub anchor: Option<String>,
+}
+
+pub struct Rendered {
+    pub html: String,
+    pub headings: Vec<Heading>,
+    pub stats: Stats,
+}
+
+/// Size of a dev log's content
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Words of prose, excluding code blocks
+    pub words: usize,
+    pub code_blocks: usize,
+    pub images: usize,
+}
+
+impl Stats {
+    /// Estimated minutes to read the prose, at least one
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE).max(1)
+    }
+
+    fn of(events: &[Event]) -> Stats {
+        let mut stats = Stats::default();
+        let mut prose = String::new();
+        // code & image alt text aren't read as prose
+        let mut skipped = 0usize;
+
+        for event in events {
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => {
+                    stats.code_blocks += 1;
+                    skipped += 1;
+                }
+                Event::Start(Tag::Image { .. }) => {
+                    stats.images += 1;
+                    skipped += 1;
+                }
+                Event::End(TagEnd::CodeBlock) | Event::End(TagEnd::Image) => {
+                    skipped = skipped.saturating_sub(1)
+                }
+                Event::Text(text) | Event::Code(text) if skipped == 0 => prose.push_str(text),
+                // words end with their block, inline markup can split a word
+                Event::SoftBreak
+                | Event::HardBreak
+                | Event::End(TagEnd::Paragraph)
+                | Event::End(TagEnd::Heading(_))
+                | Event::End(TagEnd::Item)
+                | Event::End(TagEnd::TableCell)
+                | Event::End(TagEnd::FootnoteDefinition) => prose.push(' '),
+                _ => {}
+            }
+        }
+
+        stats.words = prose.split_whitespace().count();
+
+        stats
+    }
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.words += other.words;
+        self.code_blocks += other.code_blocks;
+        self.images += other.images;
+    }
+}
+
+impl Sum for Stats {
+    fn sum<I: Iterator<Item = Stats>>(iter: I) -> Stats {
+        iter.fold(Stats::default(), |mut total, stats| {
+            total += stats;
+            total
+        })
+    }
+}
+
+/// Renders dev log markdown with the extensions enabled for a host.
+///
+/// Heading anchors are unique across everything a renderer renders, so one renderer
+/// is used per page. Dev logs are given to it in chain order, keeping the anchors of
+/// older entries the same as newer ones are added.
+pub struct Renderer<'a> {
+    setting: &'a MarkdownSetting,
+    anchors: HashSet<String>,
+    /// Where images are looked up & linked from, see [`image::img`]
+    location: Location<'a>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(setting: &'a MarkdownSetting) -> Self {
+        Renderer {
+            setting,
+            anchors: HashSet::new(),
+            location: Location::default(),
+        }
+    }
+
+    /// Sizes images & checks they exist under the host path
+    pub fn images(mut self, host_path: &'a Path) -> Self {
+        self.location.host_path = Some(host_path);
+        self
+    }
+
+    /// Makes root relative image sources absolute, for html read off the site like feeds
+    pub fn absolute(mut self, base_url: &'a str) -> Self {
+        self.location.base_url = Some(base_url);
+        self
+    }
+
+    /// Renders markdown shown at the page's url path, which relative image sources are
+    /// resolved against
+    pub fn render(&mut self, markdown: &str, page: &str) -> Rendered {
+        let (mut events, headings) = self.events(markdown);
+        let stats = Stats::of(&events);
+
+        events = rewrite_images(
+            events,
+            &Location {
+                page,
+                ..self.location
+            },
+        );
+
+        if self.setting.highlight {
+            events = highlight_code_blocks(events);
+        }
+
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+
+        Rendered {
+            html: html_output,
+            headings,
+            stats,
+        }
+    }
+
+    /// Claims the anchors of the markdown's headings without rendering it
+    pub fn skip(&mut self, markdown: &str) -> Stats {
+        Stats::of(&self.events(markdown).0)
+    }
+
+    /// Parsed markdown, with anchors added to its headings
+    fn events<'b>(&mut self, markdown: &'b str) -> (Vec<Event<'b>>, Vec<Heading>) {
+        let mut events: Vec<Event> = Parser::new_ext(markdown, options(self.setting)).collect();
+        let mut headings = Vec::new();
+
+        // explicit `{#id}`s are claimed first, so generated anchors make way for them
+        let explicit: HashMap<usize, String> = match self.setting.heading_ids {
+            true => events
+                .iter()
+                .enumerate()
+                .filter_map(|(index, event)| match event {
+                    Event::Start(Tag::Heading { id: Some(id), .. }) => {
+                        Some((index, self.claim(id.to_string())))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            false => HashMap::new(),
+        };
+
+        for index in 0..events.len() {
+            let Event::Start(Tag::Heading { level, .. }) = &events[index] else {
+                continue;
+            };
+            let level = *level as u8;
+
+            let text: String = events[index + 1..]
+                .iter()
+                .take_while(|event| !matches!(event, Event::End(TagEnd::Heading(_))))
+                .filter_map(|event| match event {
+                    Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                    _ => None,
+                })
+                .collect();
+
+            let anchor = match self.setting.heading_ids {
+                false => None,
+                true => {
+                    let anchor = match explicit.get(&index) {
+                        Some(anchor) => anchor.clone(),
+                        None => self.anchor(&text),
+                    };
+
+                    if let Event::Start(Tag::Heading { id, .. }) = &mut events[index] {
+                        *id = Some(CowStr::from(anchor.clone()));
+                    }
+
+                    Some(anchor)
+                }
+            };
+
+            headings.push(Heading {
+                level,
+                text,
+                anchor,
+            });
+        }
+
+        (events, headings)
+    }
+
+    /// Slug of the text, see [`Renderer::claim`]
+    fn anchor(&mut self, text: &str) -> String {
+        match slug(text) {
+            slug if slug.is_empty() => self.claim(String::from("section")),
+            slug => self.claim(slug),
+        }
+    }
+
+    /// Anchors already used get a `-1`, `-2`, ... suffix
+    fn claim(&mut self, base: String) -> String {
+        let mut anchor = base.clone();
+        let mut count = 0;
+
+        while !self.anchors.insert(anchor.clone()) {
+            count += 1;
+            anchor = format!("{base}-{count}");
+        }
+
+        anchor
+    }
+}
+
+/// Nested `<ul>` of links to the headings
+pub fn toc(headings: &[Heading]) -> String {
+    if headings.is_empty() {
+        return String::new();
+    }
+
+    let mut html_output = String::new();
+    let mut open: Vec<u8> = Vec::new();
+
+    for heading in headings {
+        while open.len() > 1 && open.last().is_some_and(|level| heading.level < *level) {
+            html_output.push_str("</li></ul>");
+            open.pop();
+        }
+
+        match open.last() {
+            Some(level) if heading.level <= *level => html_output.push_str("</li><li>"),
+            Some(_) => {
+                html_output.push_str("<ul><li>");
+                open.push(heading.level);
+            }
+            None => {
+                html_output.push_str("<ul class=\"toc\"><li>");
+                open.push(heading.level);
+            }
+        }
+
+        match &heading.anchor {
+            Some(anchor) => html_output.push_str(&format!(
+                "<a href=\"#{}\">{}</a>",
+                escape_html(anchor),
+                escape_html(&heading.text)
+            )),
+            None => html_output.push_str(&escape_html(&heading.text)),
+        }
+    }
+
+    for _ in open {
+        html_output.push_str("</li></ul>");
+    }
+
+    html_output
+}
+
+fn options(setting: &MarkdownSetting) -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    for (enabled, option) in [
+        (setting.tables, Options::ENABLE_TABLES),
+        (setting.footnotes, Options::ENABLE_FOOTNOTES),
+        (setting.task_lists, Options::ENABLE_TASKLISTS),
+        (setting.smart_punctuation, Options::ENABLE_SMART_PUNCTUATION),
+        (setting.heading_ids, Options::ENABLE_HEADING_ATTRIBUTES),
+    ] {
+        if enabled {
+            options.insert(option);
+        }
+    }
+
+    options
+}
+
+/// Anchor of a heading, e.g. `Caching pages (part 2)` -> `caching-pages-part-2`
+pub fn slug(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Replaces code blocks with html highlighted by the bundled syntect grammars
+fn highlight_code_blocks(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut highlighted = Vec::with_capacity(events.len());
+    let mut code_block: Option<(String, String)> = None;
+
+    for event in events {
+        code_block = match (code_block, event) {
+            (None, Event::Start(Tag::CodeBlock(kind))) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+
+                Some((language, String::new()))
+            }
+            (Some((language, mut code)), Event::Text(text)) => {
+                code.push_str(&text);
+                Some((language, code))
+            }
+            (Some((language, code)), Event::End(TagEnd::CodeBlock)) => {
+                highlighted.push(Event::Html(CowStr::from(highlight(&code, &language))));
+                None
+            }
+            (code_block, event) => {
+                highlighted.push(event);
+                code_block
+            }
+        };
+    }
+
+    highlighted
+}
+
+/// Replaces images with the `<img>`s of [`image::img`]
+fn rewrite_images<'b>(events: Vec<Event<'b>>, location: &Location) -> Vec<Event<'b>> {
+    let mut rewritten = Vec::with_capacity(events.len());
+    // src, title & alt text of the image, and how many images are nested in its alt text
+    let mut image: Option<(CowStr, CowStr, String, usize)> = None;
+
+    for event in events {
+        image = match (image, event) {
+            (
+                None,
+                Event::Start(Tag::Image {
+                    dest_url, title, ..
+                }),
+            ) => Some((dest_url, title, String::new(), 0)),
+            (Some((src, title, alt, nested)), Event::Start(Tag::Image { .. })) => {
+                Some((src, title, alt, nested + 1))
+            }
+            (Some((src, title, alt, 0)), Event::End(TagEnd::Image)) => {
+                let img = image::img(&src, &alt, &title, location);
+                rewritten.push(Event::InlineHtml(CowStr::from(img)));
+                None
+            }
+            (Some((src, title, alt, nested)), Event::End(TagEnd::Image)) => {
+                Some((src, title, alt, nested - 1))
+            }
+            (Some((src, title, mut alt, nested)), Event::Text(text) | Event::Code(text)) => {
+                alt.push_str(&text);
+                Some((src, title, alt, nested))
+            }
+            (Some((src, title, alt, nested)), Event::SoftBreak | Event::HardBreak) => {
+                Some((src, title, alt + " ", nested))
+            }
+            // the alt text is plain text
+            (Some(image), _) => Some(image),
+            (None, event) => {
+                rewritten.push(event);
+                None
+            }
+        };
+    }
+
+    rewritten
+}
+
+/// `<pre><code>` of the code with a `<span>` for each token. Unknown languages are plain text.
+fn highlight(code: &str, language: &str) -> String {
+    let syntax_set = syntax_set();
+    let syntax = syntax_set
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, CLASS_STYLE);
+
+    for line in LinesWithEndings::from(code) {
+        if let Err(err) = generator.parse_html_for_line_which_includes_newline(line) {
+            error!("Failed to highlight {language:?} code: {err}");
+            return format!("<pre><code>{}</code></pre>\n", escape_html(code));
+        }
+    }
+
+    let class = match slug(language) {
+        language if language.is_empty() => String::new(),
+        language => format!(" class=\"language-{language}\""),
+    };
+
+    format!("<pre><code{class}>{}</code></pre>\n", generator.finalize())
+}
+
+/// Stylesheet colouring highlighted code with one of syntect's bundled themes,
+/// e.g. `base16-ocean.dark` or `InspiredGitHub`
+pub fn highlight_css(theme: &str) -> Result<String, ()> {
+    let themes = ThemeSet::load_defaults();
+
+    let Some(theme) = themes.themes.get(theme) else {
+        error!(
+            "Unknown theme {theme:?}. Expected one of {:?}",
+            themes.themes.keys().collect::<Vec<&String>>()
+        );
+        return Err(());
+    };
+
+    css_for_theme_with_class_style(theme, CLASS_STYLE).map_err(|err| {
+        error!("Failed to generate css: {err}");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors(renderer: &mut Renderer, markdown: &str) -> Vec<String> {
+        renderer
+            .render(markdown, "/")
+            .headings
+            .into_iter()
+            .filter_map(|heading| heading.anchor)
+            .collect()
+    }
+
+    fn heading(level: u8, text: &str) -> Heading {
+        Heading {
+            level,
+            text: String::from(text),
+            anchor: Some(slug(text)),
+        }
+    }
+
+    #[test]
+    fn slugs_headings() {
+        assert_eq!(slug("Caching pages (part 2)"), "caching-pages-part-2");
+        assert_eq!(slug("  Über  große Dinge "), "über-große-dinge");
+        assert_eq!(slug("!?"), "");
+    }
+
+    #[test]
+    fn anchors_headings() {
+        let setting = MarkdownSetting::default();
+        let mut renderer = Renderer::new(&setting);
+
+        let rendered = renderer.render("# Intro\n\n## `cargo` build\n\n## ?!", "/");
+
+        assert_eq!(
+            rendered
+                .headings
+                .iter()
+                .map(|heading| (heading.level, heading.text.as_str()))
+                .collect::<Vec<(u8, &str)>>(),
+            [(1, "Intro"), (2, "cargo build"), (2, "?!")]
+        );
+        assert_eq!(
+            rendered
+                .headings
+                .iter()
+                .filter_map(|heading| heading.anchor.as_deref())
+                .collect::<Vec<&str>>(),
+            ["intro", "cargo-build", "section"]
+        );
+        assert!(rendered.html.contains("<h1 id=\"intro\">Intro</h1>"));
+    }
+
+    #[test]
+    fn dedups_anchors_across_renders() {
+        let setting = MarkdownSetting::default();
+        let mut renderer = Renderer::new(&setting);
+
+        assert_eq!(
+            anchors(&mut renderer, "# Intro\n# Intro"),
+            ["intro", "intro-1"]
+        );
+        renderer.skip("# Setup");
+        assert_eq!(
+            anchors(&mut renderer, "# Intro\n# Setup"),
+            ["intro-2", "setup-1"]
+        );
+    }
+
+    #[test]
+    fn dedups_explicit_anchors() {
+        let setting = MarkdownSetting::default();
+        let mut renderer = Renderer::new(&setting);
+
+        // explicit ids keep their name over generated ones, even further down
+        assert_eq!(
+            anchors(&mut renderer, "# Intro\n# Start {#intro}\n# Other {#intro}"),
+            ["intro-2", "intro", "intro-1"]
+        );
+
+        let html = renderer.render("# Again {#intro}\n# Setup", "/").html;
+        assert!(html.contains("<h1 id=\"intro-3\">Again</h1>"));
+        assert!(html.contains("<h1 id=\"setup\">Setup</h1>"));
+    }
+
+    #[test]
+    fn leaves_out_anchors_when_disabled() {
+        let setting = MarkdownSetting {
+            heading_ids: false,
+            ..MarkdownSetting::default()
+        };
+        let rendered = Renderer::new(&setting).render("# Intro {#intro}", "/");
+
+        assert_eq!(rendered.headings[0].anchor, None);
+        assert_eq!(rendered.headings[0].text, "Intro {#intro}");
+        assert_eq!(rendered.html, "<h1>Intro {#intro}</h1>\n");
+    }
+
+    #[test]
+    fn nests_toc() {
+        assert_eq!(toc(&[]), "");
+        assert_eq!(
+            toc(&[
+                heading(2, "A"),
+                heading(3, "B"),
+                heading(3, "C"),
+                heading(2, "D"),
+                heading(4, "E"),
+                heading(1, "F"),
+            ]),
+            "<ul class=\"toc\"><li><a href=\"#a\">A</a>\
+             <ul><li><a href=\"#b\">B</a></li><li><a href=\"#c\">C</a></li></ul>\
+             </li><li><a href=\"#d\">D</a>\
+             <ul><li><a href=\"#e\">E</a></li></ul>\
+             </li><li><a href=\"#f\">F</a></li></ul>"
+        );
+    }
+
+    #[test]
+    fn escapes_toc() {
+        let heading = Heading {
+            level: 1,
+            text: String::from("<b>&"),
+            anchor: None,
+        };
+
+        assert_eq!(
+            toc(&[heading]),
+            "<ul class=\"toc\"><li>&lt;b&gt;&amp;</li></ul>"
+        );
+    }
+
+    fn stats(markdown: &str) -> Stats {
+        Renderer::new(&MarkdownSetting::default()).skip(markdown)
+    }
+
+    #[test]
+    fn counts_words_of_prose() {
+        assert_eq!(stats("").words, 0);
+        assert_eq!(stats("One *two* three**four**\nfive").words, 4);
+        assert_eq!(
+            stats("# Title\n\n- one\n- two\n\n| a | b |\n|---|---|\n| c | d |").words,
+            7
+        );
+        assert_eq!(stats("Run `cargo build` first").words, 4);
+    }
+
+    #[test]
+    fn leaves_code_and_images_out_of_words() {
+        let stats =
+            stats("Some text\n\n```rust\nfn main() {}\n```\n\n![a diagram](a.png) and ![b](b.png)");
+
+        assert_eq!(
+            stats,
+            Stats {
+                words: 3,
+                code_blocks: 1,
+                images: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn estimates_reading_minutes() {
+        let stats = |words| Stats {
+            words,
+            ..Stats::default()
+        };
+
+        assert_eq!(stats(0).reading_minutes(), 1);
+        assert_eq!(stats(WORDS_PER_MINUTE).reading_minutes(), 1);
+        assert_eq!(stats(WORDS_PER_MINUTE + 1).reading_minutes(), 2);
+        assert_eq!(
+            [stats(150), stats(150)].into_iter().sum::<Stats>(),
+            stats(300)
+        );
+    }
+
+    fn render(setting: &MarkdownSetting, markdown: &str) -> String {
+        Renderer::new(setting).render(markdown, "/").html
+    }
+
+    #[test]
+    fn enables_extensions() {
+        let setting = MarkdownSetting::default();
+
+        assert!(render(&setting, "| a |\n|---|\n| b |").contains("<table>"));
+        assert!(render(&setting, "a[^1]\n\n[^1]: b").contains("footnote-definition"));
+        assert!(render(&setting, "- [x] done").contains("type=\"checkbox\""));
+        assert_eq!(render(&setting, "\"a\" -- b..."), "<p>“a” – b…</p>\n");
+        assert_eq!(render(&setting, "~~a~~"), "<p><del>a</del></p>\n");
+    }
+
+    #[test]
+    fn disables_extensions() {
+        let setting = MarkdownSetting {
+            tables: false,
+            footnotes: false,
+            task_lists: false,
+            smart_punctuation: false,
+            ..MarkdownSetting::default()
+        };
+
+        assert!(!render(&setting, "| a |\n|---|\n| b |").contains("<table>"));
+        assert!(!render(&setting, "a[^1]\n\n[^1]: b").contains("footnote-definition"));
+        assert!(!render(&setting, "- [x] done").contains("checkbox"));
+        assert_eq!(render(&setting, "\"a\" -- b..."), "<p>\"a\" -- b...</p>\n");
+    }
+
+    #[test]
+    fn highlights_code_blocks() {
+        let setting = MarkdownSetting::default();
+        let html = render(&setting, "```rust\nfn main() {}\n```");
+
+        assert!(html.starts_with("<pre><code class=\"language-rust\">"));
+        assert!(html.contains("<span class=\"hl-"));
+        assert!(html.contains("main"));
+
+        // unknown languages & indented blocks are escaped plain text
+        let html = render(&setting, "```nope\n<b>\n```\n\n    a < b");
+        assert!(html.contains("<pre><code class=\"language-nope\">"));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(html.contains("a &lt; b"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn leaves_code_blocks_when_highlighting_is_disabled() {
+        let setting = MarkdownSetting {
+            highlight: false,
+            ..MarkdownSetting::default()
+        };
+
+        assert_eq!(
+            render(&setting, "```rust\nfn main() {}\n```"),
+            "<pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn generates_highlight_css() {
+        let css = highlight_css("InspiredGitHub").unwrap();
+
+        assert!(css.contains(".hl-"));
+        assert_eq!(highlight_css("unknown"), Err(()));
+    }
+
+    #[test]
+    fn rewrites_images() {
+        let setting = MarkdownSetting::default();
+        let html = Renderer::new(&setting)
+            .absolute("http://host")
+            .render("![a *diagram* ![b](b.png)](img/a.png \"t\")", "/project/")
+            .html;
+
+        assert_eq!(
+            html,
+            "<p><img src=\"http://host/project/img/a.png\" alt=\"a diagram b\" title=\"t\" \
+             loading=\"lazy\" /></p>\n"
+        );
+    }
+}
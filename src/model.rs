@@ -0,0 +1,436 @@
+use mysql::{
+    prelude::{FromRow, FromValue},
+    FromRowError, Row, Value,
+};
+
+use serde::{Serialize, Serializer};
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::error;
+
+use crate::{
+    chain, http_date,
+    template::escape::url_component,
+    utility::{Table, UtilityCommand, UtilityData, UtilitySender},
+};
+
+const SELECT_PROJECTS: &str = "select `tag`.`id` AS `id`,`tag`.`colour` AS `colour`,`tag`.`tag_name` AS `tag_name`,`dev_log`.`body` AS `body`,`project_details`.`repo` AS  `repo`,`project_details`.`first_push` AS `first_push`,`project_details`.`last_push` AS `last_push`,`dev_log`.`tag_id` AS `summary_id` from (((`tag` join `project_details`) join `dev_log`) join `relate_tags`) where ((`tag`.`tag_type` = 1) and (`tag`.`id` = `project_details`.`proj_tag`) and (`relate_tags`.`tag_1` = `project_details`.`proj_tag`) and (`relate_tags`.`tag_2` = `dev_log`.`tag_id`))";
+pub const SELECT_TAGS: &str =
+    "SELECT `id`,`colour`,`tag_name`,`tag_type` FROM tag WHERE tag_type!=3";
+pub const SELECT_RELATED: &str = "SELECT `tag_1`,`tag_2` FROM relate_tags";
+
+/// Date (& time) read from a `DATE`/`DATETIME` column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Skill {
+    pub id: i32,
+    #[serde(serialize_with = "hex_colour")]
+    pub colour: String,
+    pub symbol: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Project {
+    #[serde(rename = "Tag")]
+    pub id: i32,
+    #[serde(serialize_with = "hex_colour")]
+    pub colour: String,
+    #[serde(rename = "Title")]
+    pub name: String,
+    #[serde(rename = "Description")]
+    pub summary: String,
+    /// Dev log holding the summary
+    #[serde(skip)]
+    pub summary_id: i32,
+    #[serde(rename = "link")]
+    pub repo: String,
+    #[serde(rename = "Start")]
+    pub first_push: Date,
+    /// `null` if unknown
+    #[serde(rename = "Update")]
+    pub last_push: Option<Date>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Tag {
+    pub id: i32,
+    #[serde(serialize_with = "hex_colour")]
+    pub colour: String,
+    #[serde(rename = "symbol")]
+    pub name: String,
+    pub tag_type: i32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Relation {
+    pub tag_1: i32,
+    pub tag_2: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct DevLog {
+    pub id: i32,
+    pub name: String,
+    pub created: Option<Date>,
+    pub body: String,
+}
+
+/// Skill tag related to a dev log
+#[derive(Clone, Debug)]
+pub struct ArticleTag {
+    pub dev_log: i32,
+    pub tag: Tag,
+}
+
+/// Row of `CHECKSUM TABLE`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableChecksum {
+    pub table: String,
+    pub checksum: Option<u64>,
+}
+
+impl DevLog {
+    /// Path segment of the dev log's permalink, also used as its anchor on the project page.
+    /// Dots are replaced too, so titles like `v1.2 release` aren't read as a file extension.
+    pub fn slug(&self) -> String {
+        format!("{}-{}", self.name.replace([' ', '.'], "_"), self.id)
+    }
+}
+
+impl Tag {
+    /// Path segment of the tag's page, e.g. `Web_Development` or `Node_js`.
+    /// Dots are replaced so the segment isn't read as a file extension.
+    pub fn slug(&self) -> String {
+        self.name.replace([' ', '.'], "_")
+    }
+
+    pub fn url(&self) -> String {
+        format!("/tag/{}", url_component(&self.slug()))
+    }
+}
+
+impl Date {
+    pub fn to_system_time(self) -> SystemTime {
+        let days = http_date::days_from_civil(self.year as i64, self.month as u32, self.day as u32);
+        let seconds =
+            days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+
+        UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64)
+    }
+
+    fn from_value(value: Value) -> Option<Option<Date>> {
+        match value {
+            Value::NULL => Some(None),
+            Value::Date(year, month, day, hour, minute, second, _ms) => Some(Some(Date {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+            })),
+            Value::Bytes(bytes) => {
+                // text protocol: "YYYY-MM-DD[ HH:MM:SS]"
+                let text = String::from_utf8(bytes).ok()?;
+                let mut parts = text
+                    .split(['-', ' ', ':', 'T'])
+                    .map(|part| part.parse::<u16>());
+
+                let year = parts.next()?.ok()?;
+                let month = parts.next()?.ok()? as u8;
+                let day = parts.next()?.ok()? as u8;
+                let hour = parts.next().unwrap_or(Ok(0)).ok()? as u8;
+                let minute = parts.next().unwrap_or(Ok(0)).ok()? as u8;
+                let second = parts.next().unwrap_or(Ok(0)).ok()? as u8;
+
+                Some(Some(Date {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    second,
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.year, self.month, self.day)
+    }
+}
+
+/// Zero padded `YYYY-MM-DD`, unlike [`Display`]
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!(
+            "{:04}-{:02}-{:02}",
+            self.year, self.month, self.day
+        ))
+    }
+}
+
+fn hex_colour<S: Serializer>(colour: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("#{colour}"))
+}
+
+fn column<T: FromValue>(row: &Row, index: usize) -> Option<T> {
+    row.get_opt::<T, usize>(index)?.ok()
+}
+
+fn date_column(row: &Row, index: usize) -> Option<Option<Date>> {
+    Date::from_value(column::<Value>(row, index)?)
+}
+
+impl FromRow for Skill {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        let (Some(id), Some(colour), Some(symbol)) =
+            (column(&row, 0), column(&row, 1), column(&row, 2))
+        else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(Skill { id, colour, symbol })
+    }
+}
+
+impl FromRow for Project {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        //id,colour,tag_name,body,repo,first_push,last_push,summary_id
+        let (
+            Some(id),
+            Some(colour),
+            Some(name),
+            Some(summary),
+            Some(repo),
+            Some(Some(first_push)),
+            Some(last_push),
+            Some(summary_id),
+        ) = (
+            column(&row, 0),
+            column(&row, 1),
+            column(&row, 2),
+            column(&row, 3),
+            column(&row, 4),
+            date_column(&row, 5),
+            date_column(&row, 6),
+            column(&row, 7),
+        )
+        else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(Project {
+            id,
+            colour,
+            name,
+            summary,
+            summary_id,
+            repo,
+            first_push,
+            last_push,
+        })
+    }
+}
+
+impl FromRow for Tag {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        //id,colour,tag_name,tag_type
+        let (Some(id), Some(colour), Some(name), Some(tag_type)) = (
+            column(&row, 0),
+            column(&row, 1),
+            column(&row, 2),
+            column(&row, 3),
+        ) else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(Tag {
+            id,
+            colour,
+            name,
+            tag_type,
+        })
+    }
+}
+
+impl FromRow for Relation {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        //tag_1,tag_2
+        let (Some(tag_1), Some(tag_2)) = (column(&row, 0), column(&row, 1)) else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(Relation { tag_1, tag_2 })
+    }
+}
+
+impl FromRow for DevLog {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        //id,tag_name,created,body
+        let (Some(id), Some(name), Some(created), Some(body)) = (
+            column(&row, 0),
+            column(&row, 1),
+            date_column(&row, 2),
+            column(&row, 3),
+        ) else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(DevLog {
+            id,
+            name,
+            created,
+            body,
+        })
+    }
+}
+
+impl FromRow for ArticleTag {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        //dev_log,id,colour,tag_name,tag_type
+        let (Some(dev_log), Some(id), Some(colour), Some(name), Some(tag_type)) = (
+            column(&row, 0),
+            column(&row, 1),
+            column(&row, 2),
+            column(&row, 3),
+            column(&row, 4),
+        ) else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(ArticleTag {
+            dev_log,
+            tag: Tag {
+                id,
+                colour,
+                name,
+                tag_type,
+            },
+        })
+    }
+}
+
+impl FromRow for TableChecksum {
+    fn from_row_opt(row: Row) -> Result<Self, FromRowError> {
+        //Table,Checksum
+        let (Some(table), Some(checksum)) = (column(&row, 0), column(&row, 1)) else {
+            return Err(FromRowError(row));
+        };
+
+        Ok(TableChecksum { table, checksum })
+    }
+}
+
+/// Sends the statement to the utility thread, returning where its rows arrive
+fn query(
+    utility_thread: &UtilitySender,
+    statement: String,
+    table: Table,
+) -> Receiver<Result<UtilityData, ()>> {
+    let (tx, rx) = mpsc::channel();
+    let _ = utility_thread.send((
+        UtilityCommand::DBQuery {
+            statement,
+            param: Vec::new(),
+            table,
+        },
+        tx,
+    ));
+
+    rx
+}
+
+/// Every project with its dev log chain, oldest dev log first
+pub fn load_projects(utility_thread: &UtilitySender) -> Result<Vec<(Project, Vec<DevLog>)>, ()> {
+    let projects_rx = query(
+        utility_thread,
+        format!("select * from ({SELECT_PROJECTS}) as p"),
+        Table::Projects,
+    );
+    let dev_log_chain_rx = query(
+        utility_thread,
+        String::from(
+            "SELECT Distinct tag_1, tag_2 FROM relate_tags, tag WHERE (tag_1 = id AND tag_type = 3) OR (tag_2 = id AND tag_type = 3)",
+        ),
+        Table::Relations,
+    );
+    let dev_logs_rx = query(
+        utility_thread,
+        String::from("SELECT id, tag_name, created, body FROM dev_log, tag WHERE id=tag_id"),
+        Table::DevLogs,
+    );
+
+    let (
+        Ok(Ok(UtilityData::Projects(projects))),
+        Ok(Ok(UtilityData::Relations(dev_log_chain))),
+        Ok(Ok(UtilityData::DevLogs(dev_logs))),
+    ) = (
+        projects_rx.recv(),
+        dev_log_chain_rx.recv(),
+        dev_logs_rx.recv(),
+    )
+    else {
+        error!("Failed to load projects");
+        return Err(());
+    };
+
+    let dev_logs: HashMap<i32, DevLog> = dev_logs
+        .into_iter()
+        .map(|dev_log| (dev_log.id, dev_log))
+        .collect();
+
+    Ok(projects
+        .into_iter()
+        .map(|proj| {
+            let chain = chain::dev_log_chain(&proj, &dev_log_chain, &dev_logs);
+
+            (proj, chain)
+        })
+        .collect())
+}
+
+/// Every tag but dev logs
+pub fn load_tags(utility_thread: &UtilitySender) -> Result<Vec<Tag>, ()> {
+    match query(utility_thread, String::from(SELECT_TAGS), Table::Tags).recv() {
+        Ok(Ok(UtilityData::Tags(tags))) => Ok(tags),
+        _ => {
+            error!("Failed to load tags");
+            Err(())
+        }
+    }
+}
+
+pub fn load_relations(utility_thread: &UtilitySender) -> Result<Vec<Relation>, ()> {
+    match query(
+        utility_thread,
+        String::from(SELECT_RELATED),
+        Table::Relations,
+    )
+    .recv()
+    {
+        Ok(Ok(UtilityData::Relations(related))) => Ok(related),
+        _ => {
+            error!("Failed to load tag relations");
+            Err(())
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::time::SystemTime;
+
+use crate::http_date;
+
+/// Ranges past this count are not worth the multipart overhead; the full file is sent instead
+const MAX_RANGES: usize = 16;
+
+/// Gaps between ranges smaller than this are sent rather than starting another part,
+/// whose boundary & headers take about as many bytes
+const MIN_GAP: u64 = 80;
+
+/// Outcome of a `Range` header against a representation of `len` bytes
+#[derive(Debug, PartialEq, Eq)]
+pub enum Ranges {
+    /// Invalid or unsupported header. Respond with the full representation.
+    Ignore,
+    /// None of the ranges overlap the representation (416)
+    Unsatisfiable,
+    /// Sorted, disjoint, inclusive byte ranges (206)
+    Satisfiable(Vec<(u64, u64)>),
+}
+
+/// Parses a `Range: bytes=...` header (RFC 9110 14.2)
+pub fn parse(range: &str, len: u64) -> Ranges {
+    let Some(range_set) = range.trim().strip_prefix("bytes=") else {
+        return Ranges::Ignore;
+    };
+
+    let mut ranges = Vec::new();
+    let mut range_specs = 0;
+
+    for range_spec in range_set.split(',').map(str::trim) {
+        if range_spec.is_empty() {
+            continue;
+        }
+        range_specs += 1;
+
+        let Some((first, last)) = range_spec.split_once('-') else {
+            return Ranges::Ignore;
+        };
+
+        let range = match (first.trim(), last.trim()) {
+            // suffix: last N bytes
+            ("", suffix) => {
+                let Ok(suffix) = suffix.parse::<u64>() else {
+                    return Ranges::Ignore;
+                };
+
+                if suffix == 0 || len == 0 {
+                    continue;
+                }
+
+                (len.saturating_sub(suffix), len - 1)
+            }
+            (first, last) => {
+                let Ok(first) = first.parse::<u64>() else {
+                    return Ranges::Ignore;
+                };
+
+                let last = match last {
+                    "" => u64::MAX,
+                    last => match last.parse::<u64>() {
+                        Ok(last) if last >= first => last,
+                        _ => return Ranges::Ignore,
+                    },
+                };
+
+                if first >= len {
+                    continue;
+                }
+
+                (first, last.min(len - 1))
+            }
+        };
+
+        ranges.push(range);
+    }
+
+    // an empty set isn't a valid header (RFC 9110 14.2)
+    if range_specs == 0 {
+        return Ranges::Ignore;
+    }
+
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+
+    // coalesce overlapping & nearby ranges
+    ranges.sort();
+    let ranges = ranges
+        .into_iter()
+        .fold(Vec::<(u64, u64)>::new(), |mut merged, (first, last)| {
+            match merged.last_mut() {
+                Some(previous) if first <= previous.1.saturating_add(MIN_GAP) => {
+                    previous.1 = previous.1.max(last)
+                }
+                _ => merged.push((first, last)),
+            }
+
+            merged
+        });
+
+    if ranges.len() > MAX_RANGES {
+        return Ranges::Ignore;
+    }
+
+    Ranges::Satisfiable(ranges)
+}
+
+/// Checks `If-Range` against the current validators. A weak tag never matches,
+/// nor does any tag while the current one isn't known.
+pub fn if_range_matches(
+    if_range: &str,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    let if_range = if_range.trim();
+
+    if if_range.starts_with('"') {
+        return Some(if_range) == etag;
+    }
+
+    if if_range.starts_with("W/") {
+        return false;
+    }
+
+    match (http_date::parse(if_range), last_modified) {
+        (Some(date), Some(last_modified)) => {
+            http_date::format(last_modified) == http_date::format(date)
+        }
+        _ => false,
+    }
+}
+
+/// `Content-Range` value of a satisfied range
+pub fn content_range((first, last): (u64, u64), len: u64) -> String {
+    format!("bytes {first}-{last}/{len}")
+}
+
+/// Builds a `multipart/byteranges` body
+pub fn multipart(
+    boundary: &str,
+    content_type: &str,
+    len: u64,
+    parts: Vec<((u64, u64), Vec<u8>)>,
+) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for (range, content) in parts {
+        body.extend_from_slice(
+            format!(
+                "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+                content_range(range, len)
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&content);
+    }
+
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(
+            parse("bytes=0-99", 1000),
+            Ranges::Satisfiable(vec![(0, 99)])
+        );
+        assert_eq!(
+            parse("bytes=900-", 1000),
+            Ranges::Satisfiable(vec![(900, 999)])
+        );
+        assert_eq!(
+            parse("bytes=-100", 1000),
+            Ranges::Satisfiable(vec![(900, 999)])
+        );
+        assert_eq!(
+            parse("bytes=-5000", 1000),
+            Ranges::Satisfiable(vec![(0, 999)])
+        );
+        assert_eq!(
+            parse("bytes=500-5000", 1000),
+            Ranges::Satisfiable(vec![(500, 999)])
+        );
+        assert_eq!(
+            parse(" bytes= 0 - 0 ", 1000),
+            Ranges::Satisfiable(vec![(0, 0)])
+        );
+    }
+
+    #[test]
+    fn coalesces_ranges() {
+        assert_eq!(
+            parse("bytes=50-99,0-49", 1000),
+            Ranges::Satisfiable(vec![(0, 99)])
+        );
+        assert_eq!(
+            parse("bytes=0-9,20-29", 1000),
+            Ranges::Satisfiable(vec![(0, 29)])
+        );
+        assert_eq!(
+            parse("bytes=0-9,500-599", 1000),
+            Ranges::Satisfiable(vec![(0, 9), (500, 599)])
+        );
+    }
+
+    #[test]
+    fn ignores_invalid_headers() {
+        assert_eq!(parse("bytes=", 1000), Ranges::Ignore);
+        assert_eq!(parse("bytes= , ,", 1000), Ranges::Ignore);
+        assert_eq!(parse("items=0-9", 1000), Ranges::Ignore);
+        assert_eq!(parse("bytes=9-0", 1000), Ranges::Ignore);
+        assert_eq!(parse("bytes=a-9", 1000), Ranges::Ignore);
+        assert_eq!(parse("bytes=0-9,10", 1000), Ranges::Ignore);
+        assert_eq!(parse("bytes=-", 1000), Ranges::Ignore);
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=0-9", 0), Ranges::Unsatisfiable);
+        assert_eq!(
+            parse("bytes=2000-2999,0-9", 1000),
+            Ranges::Satisfiable(vec![(0, 9)])
+        );
+    }
+
+    #[test]
+    fn ignores_too_many_ranges() {
+        let ranges: Vec<String> = (0..17).map(|i| format!("{0}-{0}", i * 100)).collect();
+
+        assert_eq!(
+            parse(&format!("bytes={}", ranges.join(",")), 10_000),
+            Ranges::Ignore
+        );
+    }
+
+    #[test]
+    fn matches_if_range() {
+        let modified = http_date::parse("Sun, 06 Nov 1994 08:49:37 GMT");
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+        assert!(if_range_matches("\"abc\"", Some("\"abc\""), modified));
+        assert!(!if_range_matches("\"abc\"", Some("\"abc-br\""), modified));
+        assert!(!if_range_matches("\"abc\"", None, modified));
+        assert!(!if_range_matches("W/\"abc\"", Some("W/\"abc\""), modified));
+        assert!(if_range_matches(date, None, modified));
+        assert!(!if_range_matches(
+            "Sun, 06 Nov 1994 08:49:38 GMT",
+            None,
+            modified
+        ));
+        assert!(!if_range_matches(date, Some("\"abc\""), None));
+    }
+
+    #[test]
+    fn formats_content_range() {
+        assert_eq!(content_range((0, 99), 1000), "bytes 0-99/1000");
+    }
+
+    #[test]
+    fn builds_multipart_bodies() {
+        let body = multipart(
+            "sep",
+            "text/plain",
+            20,
+            vec![((0, 1), b"ab".to_vec()), ((18, 19), b"yz".to_vec())],
+        );
+
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "\r\n--sep\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/20\r\n\r\nab\
+             \r\n--sep\r\nContent-Type: text/plain\r\nContent-Range: bytes 18-19/20\r\n\r\nyz\
+             \r\n--sep--\r\n"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use ammonia::Builder;
+use log::warn;
+
+use std::{borrow::Cow, cell::Cell, collections::HashSet, sync::OnceLock};
+
+use crate::setting::{SanitizeSetting, Setting};
+
+const EXTERNAL_LINK_REL: &str = "noopener noreferrer";
+
+/// Tags whose content is dropped along with them. They can't be allowed as well.
+const CLEAN_CONTENT_TAGS: [&str; 2] = ["script", "style"];
+
+thread_local! {
+    /// Whether the `<a>` being cleaned links to another site
+    static EXTERNAL_LINK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Cleans rendered markdown down to the allowlist in [`SanitizeSetting`].
+///
+/// Scripts, event handlers & urls with other schemes are removed and
+/// external links get `rel="noopener noreferrer"`.
+pub fn sanitize(html: &str) -> String {
+    static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+
+    SANITIZER
+        .get_or_init(|| builder(&Setting::get().sanitize))
+        .clean(html)
+        .to_string()
+}
+
+fn builder(setting: &'static SanitizeSetting) -> Builder<'static> {
+    let mut builder = Builder::empty();
+
+    // ammonia panics on tags that are also in `clean_content_tags` & on a `rel` it also sets
+    let tags: HashSet<&str> = setting
+        .tags
+        .iter()
+        .map(String::as_str)
+        .filter(|tag| match CLEAN_CONTENT_TAGS.contains(tag) {
+            true => {
+                warn!("<{tag}> can't be allowed in dev logs. Its content is always removed");
+                false
+            }
+            false => true,
+        })
+        .collect();
+    let is_rel = |tag: &str, attribute: &str| match tag == "a" && attribute == "rel" {
+        true => {
+            warn!("rel can't be allowed on <a> in dev logs. It is set on external links");
+            true
+        }
+        false => false,
+    };
+
+    builder
+        .tags(tags)
+        .generic_attributes(
+            setting
+                .attributes
+                .iter()
+                .map(String::as_str)
+                .filter(|attribute| !is_rel("a", attribute))
+                .collect(),
+        )
+        .tag_attributes(
+            setting
+                .tag_attributes
+                .iter()
+                .filter(|(tag, _)| !CLEAN_CONTENT_TAGS.contains(&tag.as_str()))
+                .map(|(tag, attributes)| {
+                    (
+                        tag.as_str(),
+                        attributes
+                            .iter()
+                            .map(String::as_str)
+                            .filter(|attribute| !is_rel(tag, attribute))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        )
+        .url_schemes(setting.url_schemes.iter().map(String::as_str).collect())
+        .clean_content_tags(CLEAN_CONTENT_TAGS.into())
+        .link_rel(Some(EXTERNAL_LINK_REL))
+        .attribute_filter(external_rel)
+        .strip_comments(true);
+
+    builder
+}
+
+/// Keeps the `rel` ammonia adds to every `<a>` on external links only.
+///
+/// The filter sees one attribute at a time. `rel` is appended after the allowed
+/// attributes, so the link's `href` has been seen by the time it is filtered.
+fn external_rel<'u>(element: &str, attribute: &str, value: &'u str) -> Option<Cow<'u, str>> {
+    match (element, attribute) {
+        ("a", "href") => EXTERNAL_LINK.with(|external| {
+            external.set(
+                value.starts_with("http://")
+                    || value.starts_with("https://")
+                    || value.starts_with("//"),
+            )
+        }),
+        ("a", "rel") if !EXTERNAL_LINK.with(|external| external.replace(false)) => return None,
+        _ => {}
+    }
+
+    Some(Cow::Borrowed(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(html: &str) -> String {
+        static SANITIZER: OnceLock<Builder<'static>> = OnceLock::new();
+
+        SANITIZER
+            .get_or_init(|| builder(Box::leak(Box::default())))
+            .clean(html)
+            .to_string()
+    }
+
+    #[test]
+    fn removes_scripts_and_handlers() {
+        assert_eq!(clean("<p>a<script>alert(1)</script></p>"), "<p>a</p>");
+        assert_eq!(clean("<style>p{}</style><p>a</p>"), "<p>a</p>");
+        assert_eq!(
+            clean("<img src=\"a.png\" onerror=\"alert(1)\">"),
+            "<img src=\"a.png\">"
+        );
+        assert_eq!(clean("<p>a<!-- b --></p>"), "<p>a</p>");
+    }
+
+    #[test]
+    fn keeps_allowed_markup() {
+        let html = "<h2 id=\"intro\">Intro</h2><p><strong>a</strong> <code>b</code></p>";
+
+        assert_eq!(clean(html), html);
+        assert_eq!(clean("<iframe src=\"x\"></iframe><p>a</p>"), "<p>a</p>");
+        assert_eq!(clean("<p style=\"color: red\">a</p>"), "<p>a</p>");
+    }
+
+    #[test]
+    fn removes_unsafe_urls() {
+        assert_eq!(clean("<a href=\"javascript:alert(1)\">a</a>"), "<a>a</a>");
+        assert_eq!(clean("<img src=\"data:image/png;base64,AA\">"), "<img>");
+    }
+
+    #[test]
+    fn sets_rel_on_external_links_only() {
+        assert_eq!(
+            clean("<a href=\"https://example.com\">a</a>"),
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer\">a</a>"
+        );
+        assert_eq!(
+            clean("<a href=\"/tag/rust\">a</a>"),
+            "<a href=\"/tag/rust\">a</a>"
+        );
+        assert_eq!(
+            clean("<a href=\"#intro\">a</a>"),
+            "<a href=\"#intro\">a</a>"
+        );
+        assert_eq!(
+            clean("<a href=\"/\" rel=\"opener\">a</a>"),
+            "<a href=\"/\">a</a>"
+        );
+    }
+
+    #[test]
+    fn ignores_tags_that_cant_be_allowed() {
+        let setting = SanitizeSetting {
+            tags: vec![String::from("p"), String::from("script"), String::from("a")],
+            attributes: vec![String::from("rel")],
+            tag_attributes: [(
+                String::from("a"),
+                vec![String::from("href"), String::from("rel")],
+            )]
+            .into(),
+            ..SanitizeSetting::default()
+        };
+
+        assert_eq!(
+            builder(Box::leak(Box::new(setting)))
+                .clean("<p>a</p><script>b</script><a href=\"/\" rel=\"me\">c</a>")
+                .to_string(),
+            "<p>a</p><a href=\"/\">c</a>"
+        );
+    }
+
+    #[test]
+    fn keeps_rel_out_of_attribute_values() {
+        assert_eq!(
+            clean("<a href=\"https://a\" title=\"a>b\">a</a>"),
+            "<a href=\"https://a\" title=\"a&gt;b\" rel=\"noopener noreferrer\">a</a>"
+        );
+    }
+}
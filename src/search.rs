@@ -0,0 +1,524 @@
+use pulldown_cmark::{Event, Parser};
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::Serialize;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{error, info};
+
+use crate::{
+    model::{self, Tag},
+    template::escape::html,
+    utility::{UtilityCommand, UtilitySender},
+};
+
+/// Weight of a term found in a title relative to the same term in the text
+const TITLE_WEIGHT: f64 = 3.0;
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+/// BM25 length normalization
+const B: f64 = 0.75;
+
+/// Wait before retrying a failed build, doubled after each failure up to [`MAX_RETRY`]
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Project,
+    DevLog,
+    Tag,
+}
+
+/// Searchable item & what's shown when it matches
+#[derive(Clone, Debug, Serialize)]
+pub struct Document {
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    pub title: String,
+    pub url: Option<String>,
+    /// Name of the project the document belongs to
+    pub project: Option<String>,
+    /// Tag names results can be filtered by
+    pub tags: Vec<String>,
+    /// Plain text, without markup
+    #[serde(skip)]
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct Hit<'a> {
+    #[serde(flatten)]
+    pub document: &'a Document,
+    pub score: f64,
+    /// Html excerpt of the text with the matched words in `<mark>`
+    pub snippet: String,
+}
+
+#[derive(Clone, Copy)]
+struct Posting {
+    document: usize,
+    title: u32,
+    text: u32,
+}
+
+/// Inverted index of stemmed terms, ranked with BM25 over the title & text of each document
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    /// (title, text) length of each document in terms
+    lengths: Vec<(usize, usize)>,
+    average_length: (f64, f64),
+    postings: HashMap<String, Vec<Posting>>,
+}
+
+impl Debug for SearchIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SearchIndex")
+            .field("documents", &self.documents.len())
+            .field("terms", &self.postings.len())
+            .finish()
+    }
+}
+
+impl SearchIndex {
+    pub fn new(documents: Vec<Document>) -> Self {
+        let mut postings: HashMap<String, Vec<Posting>> = HashMap::new();
+        let mut lengths = Vec::with_capacity(documents.len());
+
+        for (index, document) in documents.iter().enumerate() {
+            let mut counts: HashMap<String, (u32, u32)> = HashMap::new();
+
+            let title = terms(&document.title);
+            let text = terms(&document.text);
+
+            for term in &title {
+                counts.entry(term.clone()).or_default().0 += 1;
+            }
+            for term in &text {
+                counts.entry(term.clone()).or_default().1 += 1;
+            }
+
+            for (term, (title, text)) in counts {
+                postings.entry(term).or_default().push(Posting {
+                    document: index,
+                    title,
+                    text,
+                });
+            }
+
+            lengths.push((title.len(), text.len()));
+        }
+
+        let count = lengths.len().max(1) as f64;
+        let average_length = (
+            lengths.iter().map(|(title, _)| *title).sum::<usize>() as f64 / count,
+            lengths.iter().map(|(_, text)| *text).sum::<usize>() as f64 / count,
+        );
+
+        SearchIndex {
+            documents,
+            lengths,
+            average_length,
+            postings,
+        }
+    }
+
+    /// Documents matching any term of the query & tagged with every filter, best first.
+    /// Returns the number of matches along with the requested page of hits.
+    pub fn search(
+        &self,
+        query: &str,
+        tags: &[String],
+        offset: usize,
+        limit: usize,
+        snippet_words: usize,
+    ) -> (usize, Vec<Hit<'_>>) {
+        let query: HashSet<String> = terms(query).into_iter().collect();
+        let tags: Vec<String> = tags.iter().map(|tag| tag.to_lowercase()).collect();
+
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+
+        for term in &query {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+
+            let idf = {
+                let count = self.documents.len() as f64;
+                let frequency = postings.len() as f64;
+
+                (1.0 + (count - frequency + 0.5) / (frequency + 0.5)).ln()
+            };
+
+            for posting in postings {
+                let (title_length, text_length) = self.lengths[posting.document];
+
+                let frequency = TITLE_WEIGHT
+                    * normalize(posting.title, title_length, self.average_length.0)
+                    + normalize(posting.text, text_length, self.average_length.1);
+
+                *scores.entry(posting.document).or_default() +=
+                    idf * frequency * (K1 + 1.0) / (frequency + K1);
+            }
+        }
+
+        let mut matches: Vec<(usize, f64)> = scores
+            .into_iter()
+            .filter(|(document, _)| {
+                let document = &self.documents[*document];
+
+                tags.iter()
+                    .all(|tag| document.tags.iter().any(|name| name.to_lowercase() == *tag))
+            })
+            .collect();
+
+        matches.sort_by(|(document_1, score_1), (document_2, score_2)| {
+            score_2.total_cmp(score_1).then(document_1.cmp(document_2))
+        });
+
+        let hits = matches
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(document, score)| {
+                let document = &self.documents[*document];
+
+                Hit {
+                    document,
+                    score: *score,
+                    snippet: snippet(&document.text, &query, snippet_words),
+                }
+            })
+            .collect();
+
+        (matches.len(), hits)
+    }
+}
+
+/// Term frequency with BM25 length normalization
+fn normalize(frequency: u32, length: usize, average_length: f64) -> f64 {
+    if frequency == 0 {
+        return 0.0;
+    }
+
+    let average_length = average_length.max(1.0);
+
+    frequency as f64 / (1.0 - B + B * length as f64 / average_length)
+}
+
+fn stemmer() -> &'static Stemmer {
+    static STEMMER: OnceLock<Stemmer> = OnceLock::new();
+
+    STEMMER.get_or_init(|| Stemmer::create(Algorithm::English))
+}
+
+/// Words of the text with their byte offsets
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+fn term(word: &str) -> String {
+    stemmer().stem(&word.to_lowercase()).into_owned()
+}
+
+/// Lowercase, stemmed words of the text
+fn terms(text: &str) -> Vec<String> {
+    words(text).map(|(_, word)| term(word)).collect()
+}
+
+/// Html excerpt of the text around the first matched word, with every matched word marked
+fn snippet(text: &str, query: &HashSet<String>, length: usize) -> String {
+    let words: Vec<(usize, &str)> = words(text).collect();
+
+    let first_match = words
+        .iter()
+        .position(|(_, word)| query.contains(&term(word)))
+        .unwrap_or(0);
+
+    // show a little of what leads up to the match
+    let start_word = first_match.saturating_sub(length / 4);
+    let end_word = (start_word + length).min(words.len());
+
+    if start_word >= end_word {
+        return String::new();
+    }
+
+    let start = match start_word {
+        0 => 0,
+        start_word => words[start_word].0,
+    };
+    let end = match end_word == words.len() {
+        true => text.len(),
+        false => words[end_word - 1].0 + words[end_word - 1].1.len(),
+    };
+
+    let mut snippet = String::new();
+
+    if start > 0 {
+        snippet.push('…');
+    }
+
+    let mut written = start;
+    for (offset, word) in &words[start_word..end_word] {
+        if !query.contains(&term(word)) {
+            continue;
+        }
+
+        snippet.push_str(&html(&text[written..*offset]));
+        snippet.push_str(&format!("<mark>{}</mark>", html(word)));
+        written = offset + word.len();
+    }
+    snippet.push_str(&html(&text[written..end]));
+
+    if end < text.len() {
+        snippet.push('…');
+    }
+
+    snippet
+}
+
+/// Text content of markdown, as indexed & shown in snippets
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(content) | Event::Code(content) => {
+                text.push_str(&content);
+                text.push(' ');
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Projects, dev logs & tags the search index is built from. Dev logs are filtered by the
+/// tags of their project.
+fn documents(utility_thread: &UtilitySender) -> Result<Vec<Document>, ()> {
+    let projects = model::load_projects(utility_thread)?;
+    let tags = model::load_tags(utility_thread)?;
+    let related = model::load_relations(utility_thread)?;
+
+    let tag_names: HashMap<i32, &String> = tags.iter().map(|tag| (tag.id, &tag.name)).collect();
+
+    let mut documents = Vec::new();
+
+    for (proj, chain) in &projects {
+        let project_slug = proj.name.replace(" ", "_");
+
+        let project_tags: Vec<String> = related
+            .iter()
+            .filter(|relation| relation.tag_1 == proj.id && relation.tag_2 != proj.id)
+            .filter_map(|relation| tag_names.get(&relation.tag_2).map(|name| (*name).clone()))
+            .collect();
+
+        documents.push(Document {
+            kind: Kind::Project,
+            title: proj.name.clone(),
+            url: Some(format!("/{project_slug}/")),
+            project: Some(proj.name.clone()),
+            tags: project_tags.clone(),
+            text: plain_text(&proj.summary),
+        });
+
+        for dev_log in chain {
+            documents.push(Document {
+                kind: Kind::DevLog,
+                title: dev_log.name.clone(),
+                url: Some(format!("/{project_slug}/{}", dev_log.slug())),
+                project: Some(proj.name.clone()),
+                tags: project_tags.clone(),
+                text: plain_text(&dev_log.body),
+            });
+        }
+    }
+
+    // projects are already indexed as projects
+    let is_project = |tag: &Tag| projects.iter().any(|(proj, _)| proj.id == tag.id);
+
+    for tag in tags.iter().filter(|tag| !is_project(tag)) {
+        documents.push(Document {
+            kind: Kind::Tag,
+            title: tag.name.clone(),
+            url: Some(tag.url()),
+            project: None,
+            tags: vec![tag.name.clone()],
+            text: String::new(),
+        });
+    }
+
+    Ok(documents)
+}
+
+/// Spawns a thread that builds the search index at startup & rebuilds it each time the
+/// returned sender is signalled. Signals received during a rebuild are merged into one.
+///
+/// A failed build, e.g. while the database is still starting, is retried with backoff
+/// until it succeeds or a signal asks for a rebuild anyway.
+///
+/// The thread stops once the utility thread or the sender is gone.
+pub fn watch(utility_thread: UtilitySender) -> Sender<()> {
+    let (refresh, refresh_rx) = mpsc::channel::<()>();
+
+    thread::spawn(move || {
+        let mut retry = FIRST_RETRY;
+
+        loop {
+            match documents(&utility_thread) {
+                Ok(documents) => {
+                    let index = SearchIndex::new(documents);
+                    info!("Built {index:?}");
+
+                    let (tx, _rx) = mpsc::channel();
+                    let sent = utility_thread.send((
+                        UtilityCommand::SetSearchIndex {
+                            index: Arc::new(index),
+                        },
+                        tx,
+                    ));
+
+                    if sent.is_err() {
+                        return;
+                    }
+
+                    retry = FIRST_RETRY;
+
+                    if refresh_rx.recv().is_err() {
+                        return;
+                    }
+                }
+                Err(()) => {
+                    error!("Failed to build search index. Retrying in {retry:?}");
+
+                    if let Err(RecvTimeoutError::Disconnected) = refresh_rx.recv_timeout(retry) {
+                        return;
+                    }
+
+                    retry = (retry * 2).min(MAX_RETRY);
+                }
+            }
+
+            while refresh_rx.try_recv().is_ok() {}
+        }
+    });
+
+    refresh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(title: &str, text: &str, tags: &[&str]) -> Document {
+        Document {
+            kind: Kind::DevLog,
+            title: String::from(title),
+            url: None,
+            project: None,
+            tags: tags.iter().map(|tag| String::from(*tag)).collect(),
+            text: String::from(text),
+        }
+    }
+
+    fn index() -> SearchIndex {
+        SearchIndex::new(vec![
+            document("Server", "Caching pages & compressing responses", &["Rust"]),
+            document(
+                "Parser",
+                "A parser for templates, cached between requests",
+                &["Rust", "Web"],
+            ),
+            document("Caching", "Notes", &["Web"]),
+            document("Game", "Sprites & sound", &["C++"]),
+        ])
+    }
+
+    fn titles(hits: &[Hit]) -> Vec<String> {
+        hits.iter().map(|hit| hit.document.title.clone()).collect()
+    }
+
+    #[test]
+    fn ranks_title_matches_first() {
+        let index = index();
+        let (count, hits) = index.search("cache", &[], 0, 10, 20);
+
+        assert_eq!(count, 3);
+        assert_eq!(titles(&hits), ["Caching", "Server", "Parser"]);
+        assert!(hits.windows(2).all(|hits| hits[0].score >= hits[1].score));
+    }
+
+    #[test]
+    fn ranks_rare_terms_higher() {
+        let index = index();
+        let (_, hits) = index.search("templates caching", &[], 0, 10, 20);
+
+        // `templates` is only in one document, so it outweighs a second `caching`
+        assert_eq!(titles(&hits)[0], "Parser");
+        assert_eq!(index.search("unknown", &[], 0, 10, 20).0, 0);
+    }
+
+    #[test]
+    fn filters_by_every_tag() {
+        let index = index();
+
+        let (count, hits) = index.search("cache", &[String::from("web")], 0, 10, 20);
+        assert_eq!(count, 2);
+        assert_eq!(titles(&hits), ["Caching", "Parser"]);
+
+        let tags = [String::from("Rust"), String::from("Web")];
+        assert_eq!(
+            titles(&index.search("cache", &tags, 0, 10, 20).1),
+            ["Parser"]
+        );
+    }
+
+    #[test]
+    fn pages_hits() {
+        let index = index();
+        let (count, hits) = index.search("cache", &[], 1, 1, 20);
+
+        assert_eq!(count, 3);
+        assert_eq!(titles(&hits), ["Server"]);
+    }
+
+    #[test]
+    fn marks_matches_in_snippets() {
+        let query: HashSet<String> = terms("cache").into_iter().collect();
+
+        assert_eq!(
+            snippet("Caching pages & <b>cached</b> responses", &query, 20),
+            "<mark>Caching</mark> pages &amp; &lt;b&gt;<mark>cached</mark>&lt;/b&gt; responses"
+        );
+
+        let text = "one two three four five six seven eight nine cached ten eleven twelve";
+        assert_eq!(
+            snippet(text, &query, 4),
+            "…nine <mark>cached</mark> ten eleven…"
+        );
+        assert_eq!(snippet("no match here", &query, 2), "no match…");
+        assert_eq!(snippet("", &query, 2), "");
+    }
+
+    #[test]
+    fn strips_markdown() {
+        assert_eq!(
+            plain_text("# Title\n\nSome *text* with `code`\nand [a link](/a)."),
+            "Title Some text with code and a link ."
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use serde::Deserialize;
+
+use std::{collections::HashMap, fs, sync::OnceLock};
+
+use log::{error, warn};
+
+const SETTING_FILE: &str = "settings.ron";
+
+static SETTING: OnceLock<Setting> = OnceLock::new();
+
+/// Server specific settings read from `settings.ron` alongside [`pipelined_server::setting::ServerSetting`]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Setting {
+    pub utility: UtilitySetting,
+    pub compression: CompressionSetting,
+    pub sanitize: SanitizeSetting,
+    pub feed: FeedSetting,
+    pub search: SearchSetting,
+    /// Markdown extensions of each host. Hosts not listed use the defaults.
+    pub markdown: HashMap<String, MarkdownSetting>,
+}
+
+/// Worker, queue & cache sizes of the utility thread
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct UtilitySetting {
+    /// Connections opened at start up
+    pub db_pool_min: usize,
+    /// Most connections open at once. At least `db_pool_min` & 1
+    pub db_pool_max: usize,
+    pub db_workers: usize,
+    /// Jobs waiting for a DB worker. At least 1
+    pub db_queue: usize,
+    pub file_workers: usize,
+    /// Jobs waiting for a file worker. At least 1
+    pub file_queue: usize,
+    /// Milliseconds a job waits for room in a full queue before it is rejected
+    pub queue_timeout: u64,
+    /// Bytes of file content kept in memory
+    pub file_cache_size: usize,
+    /// Files larger than this (in bytes) are never cached
+    pub file_cache_max_entry: usize,
+    /// Files whose content digest is kept in memory
+    pub digest_cache_size: usize,
+    /// Seconds a rendered page is cached for. `0` disables the page cache
+    pub page_ttl: u64,
+    /// Seconds between checks of the page tables for changed rows. `0` disables the checks
+    pub page_check_interval: u64,
+}
+
+impl Default for UtilitySetting {
+    fn default() -> Self {
+        UtilitySetting {
+            db_pool_min: 1,
+            db_pool_max: 8,
+            db_workers: 4,
+            db_queue: 64,
+            file_workers: 4,
+            file_queue: 64,
+            queue_timeout: 500,
+            file_cache_size: 32 * 1024 * 1024,
+            file_cache_max_entry: 1024 * 1024,
+            digest_cache_size: 4096,
+            page_ttl: 600,
+            page_check_interval: 30,
+        }
+    }
+}
+
+/// Response compression parameters
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CompressionSetting {
+    /// Bodies smaller than this (in bytes) are sent uncompressed
+    pub min_size: usize,
+    /// Level used for both gzip & deflate (0-9)
+    pub gzip_level: u32,
+    /// Brotli quality (0-11)
+    pub brotli_quality: u32,
+}
+
+impl Default for CompressionSetting {
+    fn default() -> Self {
+        CompressionSetting {
+            min_size: 1024,
+            gzip_level: 6,
+            brotli_quality: 5,
+        }
+    }
+}
+
+/// Channel details of the dev log feeds
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FeedSetting {
+    pub title: String,
+    pub description: String,
+    /// Left out of the feeds when empty. Atom, which requires an author, names the title instead
+    pub author: String,
+    /// Most recent dev logs listed in a feed
+    pub limit: usize,
+}
+
+impl Default for FeedSetting {
+    fn default() -> Self {
+        FeedSetting {
+            title: String::from("Dev Logs"),
+            description: String::from("Progress updates on my projects"),
+            author: String::new(),
+            limit: 20,
+        }
+    }
+}
+
+/// Result limits of the search endpoint
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SearchSetting {
+    /// Results returned when the request doesn't set a limit
+    pub results: usize,
+    /// Upper bound on the limit a request can set
+    pub max_results: usize,
+    /// Words shown in a result's snippet
+    pub snippet_words: usize,
+}
+
+impl Default for SearchSetting {
+    fn default() -> Self {
+        SearchSetting {
+            results: 10,
+            max_results: 50,
+            snippet_words: 30,
+        }
+    }
+}
+
+/// Markdown extensions dev logs are rendered with
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct MarkdownSetting {
+    pub tables: bool,
+    pub footnotes: bool,
+    pub task_lists: bool,
+    /// Curly quotes, dashes & ellipses
+    pub smart_punctuation: bool,
+    /// Anchors on headings, from `{#id}` or the heading's text
+    pub heading_ids: bool,
+    /// Syntax highlighting of fenced code blocks with `hl-` prefixed classes.
+    /// See `server highlight-css` for a matching stylesheet.
+    pub highlight: bool,
+}
+
+impl Default for MarkdownSetting {
+    fn default() -> Self {
+        MarkdownSetting {
+            tables: true,
+            footnotes: true,
+            task_lists: true,
+            smart_punctuation: true,
+            heading_ids: true,
+            highlight: true,
+        }
+    }
+}
+
+/// Allowlist applied to markdown rendered dev logs
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SanitizeSetting {
+    /// `script` & `style` are ignored, as they are always removed along with their content
+    pub tags: Vec<String>,
+    /// Attributes allowed on every tag. `rel` is ignored, as external links get their own
+    pub attributes: Vec<String>,
+    pub tag_attributes: HashMap<String, Vec<String>>,
+    pub url_schemes: Vec<String>,
+    /// Dev logs by trusted authors, rendered without sanitizing
+    pub trusted_dev_logs: Vec<i32>,
+}
+
+impl Default for SanitizeSetting {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| String::from(*value)).collect();
+
+        SanitizeSetting {
+            tags: strings(&[
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "br",
+                "code",
+                "dd",
+                "del",
+                "details",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "input",
+                "kbd",
+                "li",
+                "mark",
+                "ol",
+                "p",
+                "pre",
+                "s",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "th",
+                "thead",
+                "tr",
+                "ul",
+            ]),
+            attributes: strings(&["id", "class", "title"]),
+            tag_attributes: HashMap::from([
+                (String::from("a"), strings(&["href"])),
+                (
+                    String::from("img"),
+                    strings(&[
+                        "src", "alt", "width", "height", "loading", "srcset", "sizes",
+                    ]),
+                ),
+                (
+                    String::from("input"),
+                    strings(&["type", "checked", "disabled"]),
+                ),
+                (String::from("ol"), strings(&["start"])),
+                (String::from("td"), strings(&["align"])),
+                (String::from("th"), strings(&["align"])),
+            ]),
+            url_schemes: strings(&["http", "https", "mailto"]),
+            trusted_dev_logs: Vec::new(),
+        }
+    }
+}
+
+impl Setting {
+    /// Settings shared by the whole process. Loaded on first use.
+    pub fn get() -> &'static Setting {
+        SETTING.get_or_init(Setting::load)
+    }
+
+    /// Markdown extensions of the host
+    pub fn markdown(&self, host: &str) -> MarkdownSetting {
+        self.markdown.get(host).cloned().unwrap_or_default()
+    }
+
+    fn load() -> Setting {
+        let content = match fs::read_to_string(SETTING_FILE) {
+            Ok(content) => content,
+            Err(err) => {
+                warn!("Failed to read {SETTING_FILE}: {err}. Using defaults");
+                return Setting::default();
+            }
+        };
+
+        let mut setting: Setting = match ron::from_str(&content) {
+            Ok(setting) => setting,
+            Err(err) => {
+                error!("Failed to parse {SETTING_FILE}: {err}. Using defaults");
+                return Setting::default();
+            }
+        };
+
+        setting.utility.validate();
+
+        setting
+    }
+}
+
+impl UtilitySetting {
+    /// Replaces queue sizes of 0, which would turn the queues into rendezvous channels
+    fn validate(&mut self) {
+        let default = UtilitySetting::default();
+
+        for (name, queue, default) in [
+            ("db_queue", &mut self.db_queue, default.db_queue),
+            ("file_queue", &mut self.file_queue, default.file_queue),
+        ] {
+            if *queue == 0 {
+                error!("utility.{name} must be at least 1. Using {default}");
+                *queue = default;
+            }
+        }
+
+        let db_pool_max = self.db_pool_max.max(self.db_pool_min).max(1);
+        if self.db_pool_max != db_pool_max {
+            error!("utility.db_pool_max must be at least db_pool_min & 1. Using {db_pool_max}");
+            self.db_pool_max = db_pool_max;
+        }
+    }
+}
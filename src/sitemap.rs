@@ -0,0 +1,88 @@
+use std::time::SystemTime;
+
+use crate::{http_date, template::escape::html};
+
+/// Most urls a single sitemap may list (sitemaps.org protocol)
+pub const MAX_URLS: usize = 50_000;
+
+/// Matches `sitemap.xml` & the parts of a split sitemap, `sitemap-{n}.xml` (1-based).
+/// Returns the part requested, `None` for the sitemap itself.
+pub fn route(file: &str) -> Option<Option<usize>> {
+    match file.trim_matches('/') {
+        "sitemap.xml" => Some(None),
+        file => {
+            let part = file.strip_prefix("sitemap-")?.strip_suffix(".xml")?;
+
+            match part.starts_with(|c: char| c.is_ascii_digit()) {
+                true => part.parse().ok().map(Some),
+                false => None,
+            }
+        }
+    }
+}
+
+pub struct Url {
+    pub loc: String,
+    pub lastmod: Option<SystemTime>,
+}
+
+/// Number of sitemaps needed to list the urls
+pub fn parts(urls: usize) -> usize {
+    urls.div_ceil(MAX_URLS).max(1)
+}
+
+/// `<urlset>` of the urls in the given part, `None` if there is no such part
+pub fn urlset(urls: &[Url], part: usize) -> Option<String> {
+    if part == 0 || part > parts(urls.len()) {
+        return None;
+    }
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for url in urls.iter().skip((part - 1) * MAX_URLS).take(MAX_URLS) {
+        xml.push_str(&format!("<url><loc>{}</loc>", html(&url.loc)));
+
+        if let Some(lastmod) = url.lastmod {
+            xml.push_str(&format!(
+                "<lastmod>{}</lastmod>",
+                http_date::rfc3339(lastmod)
+            ));
+        }
+
+        xml.push_str("</url>\n");
+    }
+
+    xml.push_str("</urlset>\n");
+
+    Some(xml)
+}
+
+/// `<sitemapindex>` pointing at every part of the urls, each dated by its newest url
+pub fn index(base_url: &str, urls: &[Url]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for (index, chunk) in urls.chunks(MAX_URLS).enumerate() {
+        xml.push_str(&format!(
+            "<sitemap><loc>{}/sitemap-{}.xml</loc>",
+            html(base_url),
+            index + 1
+        ));
+
+        if let Some(lastmod) = chunk.iter().filter_map(|url| url.lastmod).max() {
+            xml.push_str(&format!(
+                "<lastmod>{}</lastmod>",
+                http_date::rfc3339(lastmod)
+            ));
+        }
+
+        xml.push_str("</sitemap>\n");
+    }
+
+    xml.push_str("</sitemapindex>\n");
+
+    xml
+}
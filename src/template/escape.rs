@@ -0,0 +1,477 @@
+/// Where in an HTML document a placeholder is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Context {
+    /// Element content
+    Text,
+    /// Inside a tag, outside of attribute values: `<div {here}>`
+    Tag,
+    /// Attribute value
+    Attr {
+        kind: AttrKind,
+        quoted: bool,
+        /// Nothing precedes the placeholder in the value
+        start: bool,
+    },
+    /// `<style>` content
+    Style,
+    /// `<script>` content
+    Script,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttrKind {
+    Normal,
+    Url,
+    Css,
+    Script,
+}
+
+/// Tracks the [`Context`] at the end of the template text seen so far
+#[derive(Clone, Debug)]
+pub struct HtmlState {
+    state: State,
+    tag_name: String,
+    closing: bool,
+    attr_name: String,
+    value_len: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Text,
+    Comment,
+    /// Content of `<style>`/`<script>`, which ends at the matching closing tag only
+    RawText,
+    TagName,
+    InTag,
+    AttrName,
+    BeforeValue,
+    Value {
+        quote: Option<char>,
+    },
+}
+
+impl Default for HtmlState {
+    fn default() -> Self {
+        HtmlState {
+            state: State::Text,
+            tag_name: String::new(),
+            closing: false,
+            attr_name: String::new(),
+            value_len: 0,
+        }
+    }
+}
+
+impl HtmlState {
+    pub fn context(&self) -> Context {
+        match self.state {
+            State::Text | State::Comment => Context::Text,
+            State::RawText if self.tag_name == "script" => Context::Script,
+            State::RawText => Context::Style,
+            State::TagName | State::InTag | State::AttrName | State::BeforeValue => Context::Tag,
+            State::Value { quote } => Context::Attr {
+                kind: attr_kind(&self.attr_name),
+                quoted: quote.is_some(),
+                start: self.value_len == 0,
+            },
+        }
+    }
+
+    pub fn advance(&mut self, text: &str) {
+        let mut rest = text;
+
+        while let Some(c) = rest.chars().next() {
+            let next = &rest[c.len_utf8()..];
+
+            match self.state {
+                State::Text => {
+                    if rest.starts_with("<!--") {
+                        self.state = State::Comment;
+                        rest = &rest[4..];
+                        continue;
+                    }
+
+                    if c == '<' {
+                        let after = next.strip_prefix('/').unwrap_or(next);
+
+                        if after.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                            self.closing = next.starts_with('/');
+                            self.tag_name.clear();
+                            self.state = State::TagName;
+                            rest = after;
+                            continue;
+                        }
+                    }
+                }
+                State::Comment => {
+                    if rest.starts_with("-->") {
+                        self.state = State::Text;
+                        rest = &rest[3..];
+                        continue;
+                    }
+                }
+                State::RawText => {
+                    let closing_tag = format!("</{}", self.tag_name);
+
+                    if rest.len() >= closing_tag.len()
+                        && rest.is_char_boundary(closing_tag.len())
+                        && rest[..closing_tag.len()].eq_ignore_ascii_case(&closing_tag)
+                    {
+                        self.closing = true;
+                        self.state = State::InTag;
+                        rest = &rest[closing_tag.len()..];
+                        continue;
+                    }
+                }
+                State::TagName => match c {
+                    c if c.is_ascii_alphanumeric() || c == '-' => {
+                        self.tag_name.push(c.to_ascii_lowercase())
+                    }
+                    '>' => self.end_tag(),
+                    _ => self.state = State::InTag,
+                },
+                State::InTag => match c {
+                    '>' => self.end_tag(),
+                    c if c.is_whitespace() || c == '/' => {}
+                    c => {
+                        self.attr_name.clear();
+                        self.attr_name.push(c.to_ascii_lowercase());
+                        self.state = State::AttrName;
+                    }
+                },
+                State::AttrName => match c {
+                    '=' => self.state = State::BeforeValue,
+                    '>' => self.end_tag(),
+                    c if c.is_whitespace() || c == '/' => self.state = State::InTag,
+                    c => self.attr_name.push(c.to_ascii_lowercase()),
+                },
+                State::BeforeValue => match c {
+                    c if c.is_whitespace() => {}
+                    '>' => self.end_tag(),
+                    '"' | '\'' => {
+                        self.value_len = 0;
+                        self.state = State::Value { quote: Some(c) };
+                    }
+                    _ => {
+                        self.value_len = 1;
+                        self.state = State::Value { quote: None };
+                    }
+                },
+                State::Value { quote } => match (quote, c) {
+                    (Some(quote), c) if c == quote => self.state = State::InTag,
+                    (None, c) if c.is_whitespace() => self.state = State::InTag,
+                    (None, '>') => self.end_tag(),
+                    _ => self.value_len += 1,
+                },
+            }
+
+            rest = next;
+        }
+    }
+
+    fn end_tag(&mut self) {
+        self.state = match (self.closing, self.tag_name.as_str()) {
+            (false, "script") | (false, "style") => State::RawText,
+            _ => State::Text,
+        };
+    }
+}
+
+fn attr_kind(attr_name: &str) -> AttrKind {
+    match attr_name {
+        "href" | "src" | "action" | "formaction" | "poster" | "cite" | "background"
+        | "xlink:href" => AttrKind::Url,
+        "style" => AttrKind::Css,
+        name if name.starts_with("on") => AttrKind::Script,
+        _ => AttrKind::Normal,
+    }
+}
+
+/// Escapes a value for the context it is placed in
+pub fn escape(value: &str, context: Context) -> String {
+    match context {
+        Context::Text => html(value),
+        Context::Tag => value
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            .collect(),
+        Context::Style => css(value),
+        Context::Script => script(value),
+        Context::Attr {
+            kind,
+            quoted,
+            start,
+        } => {
+            let value = match kind {
+                AttrKind::Normal => String::from(value),
+                AttrKind::Url if start => String::from(safe_url(value)),
+                AttrKind::Url => url_component(value),
+                AttrKind::Css => css(value),
+                AttrKind::Script => script(value),
+            };
+
+            match quoted {
+                true => html(&value),
+                false => unquoted_attr(&value),
+            }
+        }
+    }
+}
+
+pub fn html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unquoted_attr(value: &str) -> String {
+    value
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                true => c.to_string(),
+                false => format!("&#x{:x};", c as u32),
+            },
+        )
+        .collect()
+}
+
+/// `#` unless the url is relative or uses a safe scheme
+fn safe_url(url: &str) -> &str {
+    let scheme = url
+        .find([':', '/', '?', '#'])
+        .filter(|end| url[*end..].starts_with(':'))
+        .map(|end| url[..end].trim().to_ascii_lowercase());
+
+    match scheme.as_deref() {
+        None | Some("http") | Some("https") | Some("mailto") | Some("tel") => url,
+        Some(_) => "#",
+    }
+}
+
+/// Percent encodes everything but unreserved characters (RFC 3986 2.3)
+pub fn url_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| {
+            match byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                true => (byte as char).to_string(),
+                false => format!("%{byte:02X}"),
+            }
+        })
+        .collect()
+}
+
+/// Decodes the `%XX` escapes of a url. Invalid escapes are kept as is.
+pub fn url_decode(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((byte, next)) = rest.split_first() {
+        if *byte == b'%' {
+            let decoded = next
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            if let Some(decoded) = decoded {
+                bytes.push(decoded);
+                rest = &next[2..];
+                continue;
+            }
+        }
+
+        bytes.push(*byte);
+        rest = next;
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Keeps only characters that can't end a declaration or open a url/function
+fn css(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, ' ' | '#' | ',' | '.' | '%' | '_' | '-')
+        })
+        .collect()
+}
+
+/// Escapes the value as the content of a js string literal
+fn script(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '"' | '\'' | '`' | '<' | '>' | '&' | '=' | '\u{2028}' | '\u{2029}' => {
+                escaped.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(template: &str) -> Context {
+        let mut state = HtmlState::default();
+        state.advance(template);
+        state.context()
+    }
+
+    #[test]
+    fn tracks_context() {
+        assert_eq!(context("<p>"), Context::Text);
+        assert_eq!(context("<!-- <a href=\""), Context::Text);
+        assert_eq!(context("<div "), Context::Tag);
+        assert_eq!(context("<style>"), Context::Style);
+        assert_eq!(context("<script>"), Context::Script);
+        assert_eq!(context("<script>var a = '<b>"), Context::Script);
+        assert_eq!(context("<script></SCRIPT>"), Context::Text);
+        assert_eq!(
+            context("<a title=\""),
+            Context::Attr {
+                kind: AttrKind::Normal,
+                quoted: true,
+                start: true
+            }
+        );
+        assert_eq!(
+            context("<a href='/tags/"),
+            Context::Attr {
+                kind: AttrKind::Url,
+                quoted: true,
+                start: false
+            }
+        );
+        assert_eq!(
+            context("<div style=\"color: "),
+            Context::Attr {
+                kind: AttrKind::Css,
+                quoted: true,
+                start: false
+            }
+        );
+        assert_eq!(
+            context("<button onclick=\""),
+            Context::Attr {
+                kind: AttrKind::Script,
+                quoted: true,
+                start: true
+            }
+        );
+        assert_eq!(
+            context("<input value=x"),
+            Context::Attr {
+                kind: AttrKind::Normal,
+                quoted: false,
+                start: false
+            }
+        );
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            escape("<b>Tom & \"Jerry\"</b>", Context::Text),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn escapes_quotes_in_attributes() {
+        let attr = |quoted| Context::Attr {
+            kind: AttrKind::Normal,
+            quoted,
+            start: true,
+        };
+
+        assert_eq!(escape("\" onload=\"x", attr(true)), "&quot; onload=&quot;x");
+        assert_eq!(escape("it's", attr(true)), "it&#39;s");
+        assert_eq!(escape("a b>c", attr(false)), "a&#x20;b&#x3e;c");
+    }
+
+    #[test]
+    fn strips_tag_names() {
+        assert_eq!(
+            escape("hidden onclick=alert(1)", Context::Tag),
+            "hiddenonclickalert1"
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_urls() {
+        let url = |start| Context::Attr {
+            kind: AttrKind::Url,
+            quoted: true,
+            start,
+        };
+
+        assert_eq!(escape("javascript:alert(1)", url(true)), "#");
+        assert_eq!(escape(" JavaScript:alert(1)", url(true)), "#");
+        assert_eq!(escape("data:text/html,<b>", url(true)), "#");
+        assert_eq!(
+            escape("https://example.com/?a=1&b=2", url(true)),
+            "https://example.com/?a=1&amp;b=2"
+        );
+        assert_eq!(escape("/projects/a:b", url(true)), "/projects/a:b");
+        assert_eq!(escape("c++ & rust", url(false)), "c%2B%2B%20%26%20rust");
+    }
+
+    #[test]
+    fn escapes_script() {
+        assert_eq!(
+            escape("</script><script>alert(1)", Context::Script),
+            "\\u003c/script\\u003e\\u003cscript\\u003ealert(1)"
+        );
+        assert_eq!(
+            escape("'; alert(\"x\")\n", Context::Script),
+            "\\u0027; alert(\\u0022x\\u0022)\\n"
+        );
+
+        let handler = Context::Attr {
+            kind: AttrKind::Script,
+            quoted: true,
+            start: false,
+        };
+
+        assert_eq!(escape("a\\'b", handler), "a\\\\\\u0027b");
+    }
+
+    #[test]
+    fn escapes_css() {
+        assert_eq!(
+            escape("red; background: url(x)", Context::Style),
+            "red background urlx"
+        );
+        assert_eq!(escape("#fff</style>", Context::Style), "#fffstyle");
+    }
+
+    #[test]
+    fn decodes_urls() {
+        assert_eq!(url_decode("a%20b%2Fc"), "a b/c");
+        assert_eq!(url_decode("100%"), "100%");
+        assert_eq!(url_decode("%zz"), "%zz");
+        assert_eq!(url_decode(&url_component("é & ü")), "é & ü");
+    }
+}
@@ -0,0 +1,68 @@
+use super::value::Value;
+
+/// Transformation applied to a placeholder's value, e.g. `{name|upper}` or `{tags|join:, }`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Upper,
+    Lower,
+    Trim,
+    /// Replaces spaces with `_`, as used in project & dev log paths
+    Slug,
+    /// Argument used when the value is null or empty
+    Default,
+    /// Concatenates a list with the argument between items
+    Join,
+    /// Number of items of a list or characters of a string
+    Length,
+}
+
+impl Filter {
+    pub fn from_name(name: &str) -> Option<Filter> {
+        match name {
+            "upper" => Some(Filter::Upper),
+            "lower" => Some(Filter::Lower),
+            "trim" => Some(Filter::Trim),
+            "slug" => Some(Filter::Slug),
+            "default" => Some(Filter::Default),
+            "join" => Some(Filter::Join),
+            "length" => Some(Filter::Length),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, value: Value, arg: Option<&str>) -> Value {
+        match self {
+            Filter::Upper => map_str(value, |value| value.to_uppercase()),
+            Filter::Lower => map_str(value, |value| value.to_lowercase()),
+            Filter::Trim => map_str(value, |value| value.trim().to_string()),
+            Filter::Slug => map_str(value, |value| value.replace(' ', "_")),
+            Filter::Default => match value.is_truthy() {
+                true => value,
+                false => Value::from(arg.unwrap_or_default()),
+            },
+            Filter::Join => match value {
+                Value::List(values) => Value::Str(
+                    values
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<String>>()
+                        .join(arg.unwrap_or_default()),
+                ),
+                value => value,
+            },
+            Filter::Length => match value {
+                Value::List(values) => Value::from(values.len()),
+                Value::Map(values) => Value::from(values.len()),
+                Value::Null => Value::Int(0),
+                value => Value::from(value.to_string().chars().count()),
+            },
+        }
+    }
+}
+
+fn map_str(value: Value, map: impl Fn(&str) -> String) -> Value {
+    match value {
+        Value::Null => Value::Null,
+        value => Value::Str(map(&value.to_string())),
+    }
+}
@@ -0,0 +1,1007 @@
+//! Templates compiled once & rendered with a [`Value`].
+//!
+//! Syntax:
+//! - `{name}`, `{project.name}`: placeholder. Unknown placeholders are a render error,
+//!   except in scripts & styles, where `{name}` is kept as text as it is more likely code
+//! - `{name|upper}`, `{tags|join:, }`: placeholder passed through [`Filter`]s
+//! - `{#for tag in tags}...{/for}`: loop over a list
+//! - `{#if ongoing}...{#else}...{/if}`, `{#if !ongoing}`: conditional. Unknown names are false
+//! - `{>partial.html.template}`: include a template from the same directory
+//!
+//! Braces that don't form a tag (css rules, js objects, ...) are kept as text,
+//! so `{placeholder}` templates written for the regex replacements still work.
+//!
+//! Placeholders are escaped for where they appear in the markup (text, attribute,
+//! url, css, script). Only [`Value::Html`] is written as is, in element content.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use log::trace;
+
+pub use self::{filter::Filter, value::Value};
+
+use self::escape::{AttrKind, Context, HtmlState};
+
+pub mod escape;
+pub mod filter;
+pub mod value;
+
+/// Includes nested deeper than this are assumed to be cyclic
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Debug)]
+pub struct Template {
+    name: String,
+    nodes: Vec<Node>,
+    /// Files the template was compiled from & their mtime at the time
+    dependencies: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Var {
+        path: Vec<String>,
+        filters: Vec<(Filter, Option<String>)>,
+        context: Context,
+        line: usize,
+    },
+    For {
+        item: String,
+        path: Vec<String>,
+        body: Vec<Node>,
+        line: usize,
+    },
+    If {
+        negate: bool,
+        path: Vec<String>,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+enum Tag {
+    Var {
+        path: Vec<String>,
+        filters: Vec<(Filter, Option<String>)>,
+    },
+    For {
+        item: String,
+        path: Vec<String>,
+    },
+    If {
+        negate: bool,
+        path: Vec<String>,
+    },
+    Else,
+    End(&'static str),
+    Include(String),
+}
+
+/// Block being parsed
+enum Frame {
+    Root,
+    For {
+        item: String,
+        path: Vec<String>,
+        line: usize,
+    },
+    If {
+        negate: bool,
+        path: Vec<String>,
+        then: Option<Vec<Node>>,
+        /// Markup context before the block, restored for `{#else}`
+        state: HtmlState,
+        line: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct TemplateError {
+    pub template: String,
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum ErrorKind {
+    Read(String),
+    UnknownPlaceholder(String),
+    UnknownFilter(String),
+    UnknownBlock(String),
+    Unclosed(&'static str),
+    Unexpected(String),
+    Include(String),
+    NotIterable(String),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: ", self.template, self.line)?;
+
+        match &self.kind {
+            ErrorKind::Read(err) => write!(f, "failed to read template: {err}"),
+            ErrorKind::UnknownPlaceholder(name) => write!(f, "unknown placeholder {{{name}}}"),
+            ErrorKind::UnknownFilter(name) => write!(f, "unknown filter |{name}"),
+            ErrorKind::UnknownBlock(tag) => write!(f, "unknown block {{{tag}}}"),
+            ErrorKind::Unclosed(block) => {
+                write!(f, "{{#{block}}} is never closed with {{/{block}}}")
+            }
+            ErrorKind::Unexpected(tag) => write!(f, "unexpected {{{tag}}}"),
+            ErrorKind::Include(name) => write!(f, "can't include {name:?}"),
+            ErrorKind::NotIterable(name) => write!(f, "{{{name}}} is not a list"),
+        }
+    }
+}
+
+impl Template {
+    /// Compiles a template file along with the partials it includes
+    pub fn load(path: &Path) -> Result<Template, TemplateError> {
+        let name = template_name(path);
+        let mut dependencies = Vec::new();
+
+        let nodes = load_nodes(
+            path,
+            &name,
+            0,
+            1,
+            &mut HtmlState::default(),
+            &mut dependencies,
+        )?;
+
+        Ok(Template {
+            name,
+            nodes,
+            dependencies,
+        })
+    }
+
+    /// Compiles a template that doesn't include partials
+    pub fn compile(name: &str, source: &str) -> Result<Template, TemplateError> {
+        let nodes = parse(
+            name,
+            source,
+            &mut HtmlState::default(),
+            &mut |include, line, _| {
+                Err(TemplateError {
+                    template: String::from(name),
+                    line,
+                    kind: ErrorKind::Include(String::from(include)),
+                })
+            },
+        )?;
+
+        Ok(Template {
+            name: String::from(name),
+            nodes,
+            dependencies: Vec::new(),
+        })
+    }
+
+    pub fn render(&self, data: &Value) -> Result<String, TemplateError> {
+        let mut output = String::new();
+
+        self.render_nodes(&self.nodes, &mut Vec::new(), data, &mut output)?;
+
+        Ok(output)
+    }
+
+    /// Checks if none of the template's files changed since it was compiled
+    pub fn is_fresh(&self) -> bool {
+        self.dependencies.iter().all(|(path, modified)| {
+            modified.is_some()
+                && fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    == *modified
+        })
+    }
+
+    fn render_nodes<'t, 'a>(
+        &'t self,
+        nodes: &'t [Node],
+        scope: &mut Vec<(&'t str, &'a Value)>,
+        data: &'a Value,
+        output: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Var {
+                    path,
+                    filters,
+                    context,
+                    line,
+                } => {
+                    let Some(value) = lookup(path, scope, data) else {
+                        // e.g. `function f(){return}`
+                        if filters.is_empty() && is_code(context) {
+                            output.push_str(&format!("{{{}}}", path.join(".")));
+                            continue;
+                        }
+
+                        return Err(
+                            self.error(*line, ErrorKind::UnknownPlaceholder(path.join(".")))
+                        );
+                    };
+
+                    let value = filters.iter().fold(value.clone(), |value, (filter, arg)| {
+                        filter.apply(value, arg.as_deref())
+                    });
+
+                    match (value, context) {
+                        (Value::Html(html), Context::Text) => output.push_str(&html),
+                        (value, context) => {
+                            output.push_str(&escape::escape(&value.to_string(), *context))
+                        }
+                    }
+                }
+                Node::For {
+                    item,
+                    path,
+                    body,
+                    line,
+                } => {
+                    let items = match lookup(path, scope, data) {
+                        Some(Value::List(items)) => items,
+                        Some(Value::Null) => continue,
+                        Some(_) => {
+                            return Err(self.error(*line, ErrorKind::NotIterable(path.join("."))))
+                        }
+                        None => {
+                            return Err(
+                                self.error(*line, ErrorKind::UnknownPlaceholder(path.join(".")))
+                            )
+                        }
+                    };
+
+                    for value in items {
+                        scope.push((item, value));
+                        let result = self.render_nodes(body, scope, data, output);
+                        scope.pop();
+
+                        result?;
+                    }
+                }
+                Node::If {
+                    negate,
+                    path,
+                    then,
+                    otherwise,
+                } => {
+                    let condition = lookup(path, scope, data).is_some_and(Value::is_truthy);
+
+                    let branch = match condition != *negate {
+                        true => then,
+                        false => otherwise,
+                    };
+
+                    self.render_nodes(branch, scope, data, output)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, line: usize, kind: ErrorKind) -> TemplateError {
+        TemplateError {
+            template: self.name.clone(),
+            line,
+            kind,
+        }
+    }
+}
+
+/// Compiled templates, recompiled once the template or one of its partials changes
+#[derive(Default)]
+pub struct TemplateCache(Mutex<HashMap<PathBuf, Arc<Template>>>);
+
+impl TemplateCache {
+    pub fn get(&self, path: &Path) -> Result<Arc<Template>, TemplateError> {
+        let cached = self
+            .0
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|template| template.is_fresh())
+            .cloned();
+
+        if let Some(template) = cached {
+            return Ok(template);
+        }
+
+        trace!("compiling {path:?}");
+        let template = Arc::new(Template::load(path)?);
+
+        self.0
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), template.clone());
+
+        Ok(template)
+    }
+}
+
+fn template_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+fn load_nodes(
+    path: &Path,
+    name: &str,
+    depth: usize,
+    line: usize,
+    state: &mut HtmlState,
+    dependencies: &mut Vec<(PathBuf, Option<SystemTime>)>,
+) -> Result<Vec<Node>, TemplateError> {
+    let error = |kind| TemplateError {
+        template: String::from(name),
+        line,
+        kind,
+    };
+
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
+    let source = fs::read_to_string(path).map_err(|err| error(ErrorKind::Read(err.to_string())))?;
+
+    dependencies.push((path.to_path_buf(), modified));
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let template = template_name(path);
+
+    parse(&template, &source, state, &mut |include, line, state| {
+        let include_error = TemplateError {
+            template: template.clone(),
+            line,
+            kind: ErrorKind::Include(String::from(include)),
+        };
+
+        // partials must stay within the template's directory
+        let is_relative = Path::new(include)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+
+        if !is_relative || depth >= MAX_INCLUDE_DEPTH {
+            return Err(include_error);
+        }
+
+        load_nodes(
+            &dir.join(include),
+            &template,
+            depth + 1,
+            line,
+            state,
+            dependencies,
+        )
+    })
+}
+
+/// Parses the template named in an include at the given line, in the including state
+type Include<'a> = dyn FnMut(&str, usize, &mut HtmlState) -> Result<Vec<Node>, TemplateError> + 'a;
+
+fn parse(
+    name: &str,
+    source: &str,
+    state: &mut HtmlState,
+    include: &mut Include,
+) -> Result<Vec<Node>, TemplateError> {
+    let error = |line, kind| TemplateError {
+        template: String::from(name),
+        line,
+        kind,
+    };
+
+    let mut stack: Vec<(Frame, Vec<Node>)> = vec![(Frame::Root, Vec::new())];
+    let mut text = String::new();
+    let mut line = 1;
+    let mut rest = source;
+
+    while let Some(open) = rest.find('{') {
+        text.push_str(&rest[..open]);
+        line += rest[..open].matches('\n').count();
+
+        let after = &rest[open + 1..];
+
+        let tag = after
+            .find(['{', '}', '\n'])
+            .filter(|end| after[*end..].starts_with('}'))
+            .map(|end| &after[..end]);
+
+        let parsed = match tag {
+            Some(tag) => parse_tag(tag).map_err(|kind| error(line, kind))?,
+            None => None,
+        };
+
+        let (Some(tag), Some(parsed)) = (tag, parsed) else {
+            // not a tag
+            text.push('{');
+            rest = after;
+            continue;
+        };
+
+        rest = &after[tag.len() + 1..];
+
+        let nodes = &mut stack.last_mut().unwrap().1;
+        if !text.is_empty() {
+            state.advance(&text);
+            nodes.push(Node::Text(std::mem::take(&mut text)));
+        }
+
+        match parsed {
+            Tag::Var { path, filters } => nodes.push(Node::Var {
+                path,
+                filters,
+                context: state.context(),
+                line,
+            }),
+            Tag::Include(partial) => {
+                let mut partial = include(&partial, line, state)?;
+                nodes.append(&mut partial);
+            }
+            Tag::For { item, path } => stack.push((Frame::For { item, path, line }, Vec::new())),
+            Tag::If { negate, path } => stack.push((
+                Frame::If {
+                    negate,
+                    path,
+                    then: None,
+                    state: state.clone(),
+                    line,
+                },
+                Vec::new(),
+            )),
+            Tag::Else => match stack.last_mut() {
+                Some((
+                    Frame::If {
+                        then: then @ None,
+                        state: before,
+                        ..
+                    },
+                    nodes,
+                )) => {
+                    *then = Some(std::mem::take(nodes));
+                    *state = before.clone();
+                }
+                _ => return Err(error(line, ErrorKind::Unexpected(String::from(tag)))),
+            },
+            Tag::End(block) => {
+                let node = match stack.pop() {
+                    Some((Frame::For { item, path, line }, body)) if block == "for" => Node::For {
+                        item,
+                        path,
+                        body,
+                        line,
+                    },
+                    Some((
+                        Frame::If {
+                            negate, path, then, ..
+                        },
+                        nodes,
+                    )) if block == "if" => match then {
+                        Some(then) => Node::If {
+                            negate,
+                            path,
+                            then,
+                            otherwise: nodes,
+                        },
+                        None => Node::If {
+                            negate,
+                            path,
+                            then: nodes,
+                            otherwise: Vec::new(),
+                        },
+                    },
+                    _ => return Err(error(line, ErrorKind::Unexpected(String::from(tag)))),
+                };
+
+                match stack.last_mut() {
+                    Some((_, nodes)) => nodes.push(node),
+                    None => return Err(error(line, ErrorKind::Unexpected(String::from(tag)))),
+                }
+            }
+        }
+
+        line += tag.matches('\n').count();
+    }
+
+    text.push_str(rest);
+    state.advance(&text);
+
+    let (frame, mut nodes) = stack.pop().unwrap();
+
+    match frame {
+        Frame::Root => {}
+        Frame::For { line, .. } => return Err(error(line, ErrorKind::Unclosed("for"))),
+        Frame::If { line, .. } => return Err(error(line, ErrorKind::Unclosed("if"))),
+    }
+
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+
+    Ok(nodes)
+}
+
+/// Parses the inside of `{...}`. `Ok(None)` if it isn't a tag.
+fn parse_tag(tag: &str) -> Result<Option<Tag>, ErrorKind> {
+    if let Some(block) = tag.strip_prefix('#') {
+        let words: Vec<&str> = block.split_whitespace().collect();
+
+        let parsed = match words.as_slice() {
+            ["for", item, "in", path] if is_ident(item) => parse_path(path).map(|path| Tag::For {
+                item: String::from(*item),
+                path,
+            }),
+            ["if", path] => {
+                let (negate, path) = match path.strip_prefix('!') {
+                    Some(path) => (true, path),
+                    None => (false, *path),
+                };
+
+                parse_path(path).map(|path| Tag::If { negate, path })
+            }
+            ["else"] => Some(Tag::Else),
+            _ => None,
+        };
+
+        return parsed
+            .map(Some)
+            .ok_or_else(|| ErrorKind::UnknownBlock(String::from(tag)));
+    }
+
+    match tag {
+        "/for" => return Ok(Some(Tag::End("for"))),
+        "/if" => return Ok(Some(Tag::End("if"))),
+        _ => {}
+    }
+
+    if let Some(partial) = tag.strip_prefix('>') {
+        let partial = partial.trim();
+
+        let valid = !partial.is_empty()
+            && partial
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'));
+
+        return match valid {
+            true => Ok(Some(Tag::Include(String::from(partial)))),
+            false => Err(ErrorKind::Include(String::from(partial))),
+        };
+    }
+
+    let mut parts = tag.split('|');
+
+    let Some(path) = parts.next().and_then(parse_path) else {
+        return Ok(None);
+    };
+
+    let mut filters = Vec::new();
+
+    for filter in parts {
+        let (filter_name, arg) = match filter.split_once(':') {
+            Some((filter_name, arg)) => (filter_name, Some(String::from(arg))),
+            None => (filter, None),
+        };
+
+        if !is_ident(filter_name) {
+            return Ok(None);
+        }
+
+        let Some(filter) = Filter::from_name(filter_name) else {
+            return Err(ErrorKind::UnknownFilter(String::from(filter_name)));
+        };
+
+        filters.push((filter, arg));
+    }
+
+    Ok(Some(Tag::Var { path, filters }))
+}
+
+fn parse_path(path: &str) -> Option<Vec<String>> {
+    let path: Vec<String> = path.split('.').map(String::from).collect();
+
+    path.iter().all(|segment| is_ident(segment)).then_some(path)
+}
+
+fn is_ident(ident: &str) -> bool {
+    let mut chars = ident.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Script or style, inline or in an attribute
+fn is_code(context: &Context) -> bool {
+    matches!(
+        context,
+        Context::Script
+            | Context::Style
+            | Context::Attr {
+                kind: AttrKind::Script | AttrKind::Css,
+                ..
+            }
+    )
+}
+
+fn lookup<'a>(path: &[String], scope: &[(&str, &'a Value)], data: &'a Value) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+
+    let value = scope
+        .iter()
+        .rev()
+        .find(|(name, _)| name == first)
+        .map(|(_, value)| *value)
+        .or_else(|| data.get(first))?;
+
+    rest.iter().try_fold(value, |value, key| value.get(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// Context of each placeholder, in template order
+    fn contexts(source: &str) -> Vec<Context> {
+        fn collect(nodes: &[Node], contexts: &mut Vec<Context>) {
+            for node in nodes {
+                match node {
+                    Node::Text(_) => {}
+                    Node::Var { context, .. } => contexts.push(*context),
+                    Node::For { body, .. } => collect(body, contexts),
+                    Node::If {
+                        then, otherwise, ..
+                    } => {
+                        collect(then, contexts);
+                        collect(otherwise, contexts);
+                    }
+                }
+            }
+        }
+
+        let mut contexts = Vec::new();
+        collect(
+            &Template::compile("test", source).unwrap().nodes,
+            &mut contexts,
+        );
+
+        contexts
+    }
+
+    fn attr(kind: AttrKind, quoted: bool, start: bool) -> Context {
+        Context::Attr {
+            kind,
+            quoted,
+            start,
+        }
+    }
+
+    #[test]
+    fn detects_text() {
+        assert_eq!(
+            contexts("<p>{a}</p>{b}<!-- <a href=\"{c}\"> -->"),
+            [Context::Text, Context::Text, Context::Text]
+        );
+    }
+
+    #[test]
+    fn detects_attributes() {
+        assert_eq!(
+            contexts("<p title=\"{a}\" class='post {b}' data-id=x{c} {d}>"),
+            [
+                attr(AttrKind::Normal, true, true),
+                attr(AttrKind::Normal, true, false),
+                attr(AttrKind::Normal, false, false),
+                Context::Tag,
+            ]
+        );
+        assert_eq!(contexts("<p title=\"a > b\">{a}</p>"), [Context::Text]);
+    }
+
+    #[test]
+    fn detects_url_attributes() {
+        assert_eq!(
+            contexts("<a href=\"{a}\"><img src=\"/media/{b}\" alt=\"{c}\"></a>"),
+            [
+                attr(AttrKind::Url, true, true),
+                attr(AttrKind::Url, true, false),
+                attr(AttrKind::Normal, true, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_scripts_and_styles() {
+        assert_eq!(
+            contexts(
+                "<script>let a = \"{a}\"; if (b < c) {}</script>{b}\
+                 <style>p { color: {c}; }</style>{d}"
+            ),
+            [
+                Context::Script,
+                Context::Text,
+                Context::Style,
+                Context::Text
+            ]
+        );
+        assert_eq!(
+            contexts("<script>document.write(\"<p>{a}</p>\")</script>"),
+            [Context::Script]
+        );
+        assert_eq!(
+            contexts("<button onclick=\"go({a})\" style=\"color: {b}\">"),
+            [
+                attr(AttrKind::Script, true, false),
+                attr(AttrKind::Css, true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_contexts_in_blocks() {
+        assert_eq!(
+            contexts("{#for tag in tags}<a href=\"{tag.url}\">{tag.name}</a>{/for}"),
+            [attr(AttrKind::Url, true, true), Context::Text]
+        );
+        assert_eq!(
+            contexts("<a {#if url}href=\"{url}\"{#else}title=\"{name}\"{/if}>{name}</a>"),
+            [
+                attr(AttrKind::Url, true, true),
+                attr(AttrKind::Normal, true, true),
+                Context::Text,
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_html_as_is() {
+        let template = Template::compile("test", "<p>{value}</p><p title=\"{value}\">").unwrap();
+        let value = Value::map([("value", Value::Html(String::from("<i>x</i>")))]);
+
+        assert_eq!(
+            template.render(&value).unwrap(),
+            "<p><i>x</i></p><p title=\"&lt;i&gt;x&lt;/i&gt;\">"
+        );
+    }
+
+    #[test]
+    fn restores_context_for_else() {
+        let template = Template::compile(
+            "test",
+            "<a {#if value}href=\"{value}\"{#else}title=\"{value}\"{/if}>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            template
+                .render(&Value::map([("value", Value::from("javascript:x"))]))
+                .unwrap(),
+            "<a href=\"#\">"
+        );
+        assert_eq!(
+            template
+                .render(&Value::map([("value", Value::from(""))]))
+                .unwrap(),
+            "<a title=\"\">"
+        );
+    }
+
+    fn render(source: &str, data: &Value) -> Result<String, TemplateError> {
+        Template::compile("test", source)?.render(data)
+    }
+
+    fn tags() -> Value {
+        Value::map([(
+            "tags",
+            Value::List(vec![
+                Value::map([("name", Value::from("rust")), ("id", Value::from(1))]),
+                Value::map([("name", Value::from("web dev")), ("id", Value::from(2))]),
+            ]),
+        )])
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let data = Value::map([(
+            "project",
+            Value::map([
+                ("name", Value::from("Portfolio")),
+                ("stars", Value::from(3)),
+            ]),
+        )]);
+
+        assert_eq!(
+            render("<h1>{project.name}</h1> {project.stars}", &data).unwrap(),
+            "<h1>Portfolio</h1> 3"
+        );
+        assert_eq!(
+            render("{ not a tag } {}", &data).unwrap(),
+            "{ not a tag } {}"
+        );
+    }
+
+    #[test]
+    fn renders_loops() {
+        assert_eq!(
+            render(
+                "{#for tag in tags}<li id=\"{tag.id}\">{tag.name}</li>{/for}",
+                &tags()
+            )
+            .unwrap(),
+            "<li id=\"1\">rust</li><li id=\"2\">web dev</li>"
+        );
+        // the loop variable shadows the data & goes out of scope after the loop
+        assert_eq!(
+            render("{#for tags in tags}{tags.id}{/for}{tags|length}", &tags()).unwrap(),
+            "122"
+        );
+        assert_eq!(
+            render(
+                "{#for tag in none}{tag}{/for}",
+                &Value::map([("none", Value::Null)])
+            )
+            .unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let template = Template::compile(
+            "test",
+            "{#if ongoing}ongoing{#else}done{/if}{#if !tags}!{/if}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            template
+                .render(&Value::map([("ongoing", Value::Bool(true))]))
+                .unwrap(),
+            "ongoing!"
+        );
+        assert_eq!(template.render(&tags()).unwrap(), "done");
+    }
+
+    #[test]
+    fn applies_filters() {
+        let data = Value::map([
+            ("name", Value::from(" Web Dev ")),
+            ("empty", Value::from("")),
+        ]);
+
+        assert_eq!(
+            render("{name|trim|upper} {name|trim|slug|lower}", &data).unwrap(),
+            "WEB DEV web_dev"
+        );
+        assert_eq!(render("{empty|default:none}", &data).unwrap(), "none");
+        assert_eq!(
+            render("{#for tag in tags}{tag.name}{/for} {tags|length}", &tags()).unwrap(),
+            "rustweb dev 2"
+        );
+        assert!(matches!(
+            Template::compile("test", "{name|shout}"),
+            Err(TemplateError {
+                kind: ErrorKind::UnknownFilter(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn includes_partials() {
+        let dir = env::temp_dir().join(format!("template_includes_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("page.html.template"),
+            "<ul>{>tag.html.template}</ul>",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("tag.html.template"),
+            "{#for tag in tags}<li>{tag.name}</li>{/for}",
+        )
+        .unwrap();
+        fs::write(dir.join("loop.html.template"), "{>loop.html.template}").unwrap();
+        fs::write(dir.join("escape.html.template"), "{>../page.html.template}").unwrap();
+
+        let page = Template::load(&dir.join("page.html.template")).unwrap();
+        let cyclic = Template::load(&dir.join("loop.html.template"));
+        let escaping = Template::load(&dir.join("escape.html.template"));
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            page.render(&tags()).unwrap(),
+            "<ul><li>rust</li><li>web dev</li></ul>"
+        );
+        assert!(matches!(
+            cyclic,
+            Err(TemplateError {
+                kind: ErrorKind::Include(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            escaping,
+            Err(TemplateError {
+                kind: ErrorKind::Include(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Template::compile("test", "{>tag.html.template}"),
+            Err(TemplateError {
+                kind: ErrorKind::Include(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_unknown_placeholders() {
+        let error = render("<p>\n{project.name}</p>", &tags()).unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert!(
+            matches!(error.kind, ErrorKind::UnknownPlaceholder(ref name) if name == "project.name")
+        );
+        assert!(matches!(
+            render("{#for tag in missing}{/for}", &tags()),
+            Err(TemplateError {
+                kind: ErrorKind::UnknownPlaceholder(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            render("{#for tag in tags}{tag.name.first}{/for}", &tags()),
+            Err(TemplateError {
+                kind: ErrorKind::UnknownPlaceholder(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn keeps_unknown_placeholders_in_code() {
+        assert_eq!(
+            render(
+                "<script>function f(){return}</script><style>p{color}</style>",
+                &tags()
+            )
+            .unwrap(),
+            "<script>function f(){return}</script><style>p{color}</style>"
+        );
+        assert_eq!(
+            render("<a onclick=\"if (a) {go}\">", &tags()).unwrap(),
+            "<a onclick=\"if (a) {go}\">"
+        );
+        assert!(render("<script>{go|upper}</script>", &tags()).is_err());
+    }
+
+    #[test]
+    fn reports_unbalanced_blocks() {
+        let error = |source| Template::compile("test", source).unwrap_err().kind;
+
+        assert!(matches!(
+            error("{#for tag in tags}"),
+            ErrorKind::Unclosed("for")
+        ));
+        assert!(matches!(error("{#if a}{/for}"), ErrorKind::Unexpected(_)));
+        assert!(matches!(error("{#else}"), ErrorKind::Unexpected(_)));
+        assert!(matches!(error("{#while a}"), ErrorKind::UnknownBlock(_)));
+    }
+}
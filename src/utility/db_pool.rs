@@ -0,0 +1,198 @@
+use mysql::{prelude::Queryable, Conn, Opts};
+
+use std::{
+    env,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{error, trace, warn};
+
+const CHECKOUT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connections idle for longer are health checked before being handed out
+const HEALTH_CHECK_IDLE: Duration = Duration::from_secs(30);
+
+/// Pool of MySQL connections shared by the utility thread's DB workers.
+///
+/// Connections that sat idle for a while are health checked before being handed out;
+/// a dead connection is dropped and replaced by a fresh one without the caller noticing.
+pub struct DbPool {
+    opts: Opts,
+    min_size: usize,
+    max_size: usize,
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+
+struct PoolState {
+    /// Connections & when they were last released
+    idle: Vec<(Instant, Conn)>,
+    open: usize,
+}
+
+/// Connection checked out of a [`DbPool`]. Returned to the pool on drop.
+pub struct PooledConn {
+    pool: Arc<DbPool>,
+    conn: Option<Conn>,
+}
+
+impl DbPool {
+    /// Builds a pool connecting with the credentials in the `DB_*` env variables
+    pub fn from_env(min_size: usize, max_size: usize) -> Result<Arc<DbPool>, ()> {
+        let url = {
+            let Ok(host) = env::var("DB_host") else {
+                error!("DB_host is not set");
+                return Err(());
+            };
+            let Ok(name) = env::var("DB_name") else {
+                error!("DB_name is not set");
+                return Err(());
+            };
+            let Ok(user_name) = env::var("DB_username") else {
+                error!("DB_username is not set");
+                return Err(());
+            };
+            let Ok(password) = env::var("DB_password") else {
+                error!("DB_password is not set");
+                return Err(());
+            };
+            let Ok(port) = env::var("DB_port") else {
+                error!("DB_port is not set");
+                return Err(());
+            };
+
+            format!("mysql://{user_name}:{password}@{host}:{port}/{name}")
+        };
+
+        let opts = match Opts::from_url(&url) {
+            Ok(opts) => opts,
+            Err(err) => {
+                error!("Failed to get url: {err}");
+                return Err(());
+            }
+        };
+
+        Ok(DbPool::new(opts, min_size, max_size))
+    }
+
+    pub fn new(opts: Opts, min_size: usize, max_size: usize) -> Arc<DbPool> {
+        let pool = Arc::new(DbPool {
+            opts,
+            min_size,
+            max_size,
+            state: Mutex::new(PoolState {
+                idle: Vec::with_capacity(max_size),
+                open: 0,
+            }),
+            released: Condvar::new(),
+        });
+
+        pool.fill();
+
+        pool
+    }
+
+    /// Opens connections until the pool holds at least `min_size`.
+    fn fill(&self) {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.open >= self.min_size {
+                    return;
+                }
+                state.open += 1;
+            }
+
+            match Conn::new(self.opts.clone()) {
+                Ok(conn) => {
+                    self.state.lock().unwrap().idle.push((Instant::now(), conn));
+                    self.released.notify_one();
+                }
+                Err(err) => {
+                    error!("Failed to conn: {err}");
+                    self.state.lock().unwrap().open -= 1;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Checks out a live connection, blocking while the pool is at `max_size`.
+    pub fn get(self: &Arc<Self>) -> Result<PooledConn, ()> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some((released, mut conn)) = state.idle.pop() {
+                drop(state);
+
+                if released.elapsed() < HEALTH_CHECK_IDLE || conn.query_drop("SELECT 1").is_ok() {
+                    return Ok(PooledConn {
+                        pool: self.clone(),
+                        conn: Some(conn),
+                    });
+                }
+
+                warn!("Dropping dead connection");
+                state = self.state.lock().unwrap();
+                state.open -= 1;
+                continue;
+            }
+
+            if state.open < self.max_size {
+                state.open += 1;
+                drop(state);
+
+                trace!("Opening new connection");
+                return match Conn::new(self.opts.clone()) {
+                    Ok(conn) => Ok(PooledConn {
+                        pool: self.clone(),
+                        conn: Some(conn),
+                    }),
+                    Err(err) => {
+                        error!("Failed to conn: {err}");
+                        self.state.lock().unwrap().open -= 1;
+                        self.released.notify_one();
+                        Err(())
+                    }
+                };
+            }
+
+            let (next_state, timeout) =
+                self.released.wait_timeout(state, CHECKOUT_TIMEOUT).unwrap();
+            state = next_state;
+
+            if timeout.timed_out() && state.idle.is_empty() && state.open >= self.max_size {
+                error!("Timed out waiting for a connection");
+                return Err(());
+            }
+        }
+    }
+
+    fn release(&self, conn: Conn) {
+        self.state.lock().unwrap().idle.push((Instant::now(), conn));
+        self.released.notify_one();
+    }
+}
+
+impl Deref for PooledConn {
+    type Target = Conn;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConn {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConn {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.release(conn);
+        }
+    }
+}
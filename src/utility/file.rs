@@ -0,0 +1,190 @@
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use log::error;
+
+use super::{file_cache::FileCache, UtilityData};
+
+/// Content digests of files, reused until the file's size or mtime change.
+/// The least recently used are dropped once the cache is full.
+pub struct DigestCache(Mutex<LruCache<PathBuf, FileDigest>>);
+
+impl DigestCache {
+    /// Keeps the digests of up to `capacity` files, at least 1
+    pub fn new(capacity: usize) -> Self {
+        DigestCache(Mutex::new(LruCache::new(
+            NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+        )))
+    }
+}
+
+#[derive(Clone)]
+struct FileDigest {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: String,
+}
+
+pub fn read_file(path: &Path, byte_cond: bool, cache: &FileCache) -> Result<UtilityData, ()> {
+    //check if file exists
+    if !path.is_file() {
+        return Err(());
+    }
+
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return Err(());
+    };
+
+    let content = match (ext, byte_cond) {
+        ("txt", false) | ("html", false) | ("css", false) | ("template", false) | (_, true) => {
+            match cache.read(path) {
+                Ok(content) => content,
+                Err(err) => {
+                    error!("Failed to read {path:?}: {err}");
+                    return Err(());
+                }
+            }
+        }
+        _ => return Err(()),
+    };
+
+    if byte_cond {
+        return Ok(UtilityData::Bytes(content));
+    }
+
+    match std::str::from_utf8(&content) {
+        Ok(content) => Ok(UtilityData::String(vec![String::from(content)])),
+        Err(err) => {
+            error!("{path:?} is not utf-8: {err}");
+            Err(())
+        }
+    }
+}
+
+/// Reads only the given inclusive byte ranges of a file
+pub fn read_ranges(path: &Path, ranges: &[(u64, u64)]) -> Result<UtilityData, ()> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Err(()),
+    };
+
+    let mut read_range = |(first, last): (u64, u64)| -> io::Result<Vec<u8>> {
+        let mut content = vec![0; (last - first + 1) as usize];
+
+        file.seek(SeekFrom::Start(first))?;
+        file.read_exact(&mut content)?;
+
+        Ok(content)
+    };
+
+    ranges
+        .iter()
+        .map(|range| read_range(*range))
+        .collect::<io::Result<Vec<Vec<u8>>>>()
+        .map(UtilityData::Ranges)
+        .map_err(|err| error!("Failed to read {ranges:?} of {path:?}: {err}"))
+}
+
+/// Size, modification time & digest of a file. The file is only read to hash it if
+/// `digest` is set & the cached digest is stale.
+pub fn read_metadata(path: &Path, digest: bool, digests: &DigestCache) -> Result<UtilityData, ()> {
+    let metadata = match path.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return Err(()),
+    };
+
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+
+    let cached = digests
+        .0
+        .lock()
+        .unwrap()
+        .get(path)
+        .filter(|cached| modified.is_some() && cached.modified == modified && cached.len == len)
+        .cloned();
+
+    let hash = match cached {
+        Some(cached) => Some(cached.hash),
+        None if !digest => None,
+        None => {
+            let hash = match hash_file(path) {
+                Ok(hash) => hash,
+                Err(err) => {
+                    error!("Failed to hash {path:?}: {err}");
+                    return Err(());
+                }
+            };
+
+            let digest = FileDigest {
+                len,
+                modified,
+                hash,
+            };
+
+            digests
+                .0
+                .lock()
+                .unwrap()
+                .put(path.to_path_buf(), digest.clone());
+
+            Some(digest.hash)
+        }
+    };
+
+    Ok(UtilityData::Metadata {
+        len,
+        modified,
+        hash,
+    })
+}
+
+/// Hex encoded (truncated) SHA-256 of the content
+pub fn hash_content(content: &[u8]) -> String {
+    to_hex(&Sha256::digest(content))
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .take(16)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Collects every file under the directory, recursively
+pub fn find_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("Failed to read {dir:?}: {err}");
+            return;
+        }
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_files(&path, files);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use lru::LruCache;
+use serde::Serialize;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use log::trace;
+
+/// Least recently used file contents, bounded by their total size in bytes.
+///
+/// Entries are keyed by canonical path & dropped once the file's size or mtime change.
+/// Contents are shared, so a hit doesn't copy the file.
+pub struct FileCache {
+    budget: usize,
+    max_entry: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Entries {
+    files: LruCache<PathBuf, CachedFile>,
+    bytes: usize,
+}
+
+struct CachedFile {
+    len: u64,
+    modified: SystemTime,
+    content: Arc<[u8]>,
+}
+
+/// Snapshot of the [`FileCache`] counters
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct FileCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl FileCache {
+    /// `budget`: total bytes kept in memory. `max_entry`: larger files are always read from disk
+    pub fn new(budget: usize, max_entry: usize) -> Self {
+        FileCache {
+            budget,
+            max_entry: max_entry.min(budget),
+            entries: Mutex::new(Entries {
+                files: LruCache::unbounded(),
+                bytes: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Content of the file, from memory if it has not changed since it was cached
+    pub fn read(&self, path: &Path) -> io::Result<Arc<[u8]>> {
+        let path = path.canonicalize()?;
+        let metadata = fs::metadata(&path)?;
+
+        let len = metadata.len();
+        let modified = metadata.modified().ok();
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+
+            let fresh = match entries.files.get(&path) {
+                Some(cached) if Some(cached.modified) == modified && cached.len == len => {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+
+                    return Ok(cached.content.clone());
+                }
+                Some(_) => false,
+                None => true,
+            };
+
+            if !fresh {
+                trace!("{path:?} changed; evicted");
+                entries.remove(&path);
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let content: Arc<[u8]> = fs::read(&path)?.into();
+
+        // files without an mtime can't be validated
+        let Some(modified) = modified else {
+            return Ok(content);
+        };
+
+        if content.len() > self.max_entry || content.len() as u64 != len {
+            return Ok(content);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.remove(&path);
+        entries.bytes += content.len();
+        entries.files.put(
+            path,
+            CachedFile {
+                len,
+                modified,
+                content: content.clone(),
+            },
+        );
+
+        while entries.bytes > self.budget {
+            let Some((path, evicted)) = entries.files.pop_lru() else {
+                break;
+            };
+
+            trace!("{path:?} evicted");
+            entries.bytes -= evicted.content.len();
+        }
+
+        Ok(content)
+    }
+
+    pub fn stats(&self) -> FileCacheStats {
+        let entries = self.entries.lock().unwrap();
+
+        FileCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: entries.files.len(),
+            bytes: entries.bytes,
+        }
+    }
+}
+
+impl Entries {
+    fn remove(&mut self, path: &Path) {
+        if let Some(removed) = self.files.pop(path) {
+            self.bytes -= removed.content.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn shares_cached_contents() {
+        let dir = env::temp_dir().join(format!("file_cache_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "aaaa").unwrap();
+        fs::write(&b, "bbbbbb").unwrap();
+
+        let cache = FileCache::new(8, 8);
+
+        let first = cache.read(&a).unwrap();
+        let second = cache.read(&a).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // b doesn't fit alongside a
+        assert_eq!(&*cache.read(&b).unwrap(), b"bbbbbb");
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+        assert_eq!((stats.entries, stats.bytes), (1, 6));
+
+        fs::write(&b, "bb").unwrap();
+        assert_eq!(&*cache.read(&b).unwrap(), b"bb");
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cache.stats().bytes, 2);
+    }
+}
@@ -0,0 +1,391 @@
+use mysql::{
+    prelude::{FromRow, Queryable},
+    Row,
+};
+
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use log::{error, info, trace};
+
+use crate::{
+    model::{ArticleTag, DevLog, Project, Relation, Skill, TableChecksum, Tag},
+    search::{self, SearchIndex},
+    setting::UtilitySetting,
+    template::{Template, TemplateCache},
+};
+
+use self::{
+    db_pool::DbPool,
+    file::{read_file, read_metadata, read_ranges, DigestCache},
+    file_cache::{FileCache, FileCacheStats},
+    page_cache::{Page, PageCache},
+    worker_pool::WorkerPool,
+};
+
+pub mod db_pool;
+pub mod file;
+pub mod file_cache;
+pub mod page_cache;
+pub mod worker_pool;
+
+#[derive(Clone, Debug)]
+pub enum UtilityCommand {
+    GetFile {
+        file: PathBuf,
+        bytes: bool,
+    },
+    /// Inclusive byte ranges of a file. Only the requested bytes are read.
+    GetFileRanges {
+        file: PathBuf,
+        ranges: Vec<(u64, u64)>,
+    },
+    /// Size & modification time of a file. Its content is only hashed if `digest` is set,
+    /// otherwise the digest is included only if it is already known.
+    GetMetadata {
+        file: PathBuf,
+        digest: bool,
+    },
+    DBQuery {
+        statement: String,
+        param: Vec<String>,
+        table: Table,
+    },
+    /// Rendered page cached under the slug, if it hasn't expired
+    GetPage {
+        slug: String,
+    },
+    CachePage {
+        slug: String,
+        page: Page,
+    },
+    /// Drops every cached page & rebuilds the search index
+    InvalidatePages,
+    /// Latest search index. Fails until the first index is built.
+    GetSearchIndex,
+    /// Replaces the search index
+    SetSearchIndex {
+        index: Arc<SearchIndex>,
+    },
+    /// Hit & miss counters of the file cache
+    FileCacheStats,
+    /// Template compiled from the file & the partials it includes
+    GetTemplate {
+        file: PathBuf,
+    },
+    /// Stops the worker pools once their queued jobs finish
+    Shutdown,
+}
+
+/// Row type a [`UtilityCommand::DBQuery`] statement returns
+#[derive(Clone, Copy, Debug)]
+pub enum Table {
+    Skills,
+    Projects,
+    Tags,
+    Relations,
+    DevLogs,
+    ArticleTags,
+    Checksums,
+}
+
+#[derive(Clone, Debug)]
+pub enum UtilityData {
+    /// Shared with the file cache
+    Bytes(Arc<[u8]>),
+    String(Vec<String>),
+    Skills(Vec<Skill>),
+    Projects(Vec<Project>),
+    Tags(Vec<Tag>),
+    Relations(Vec<Relation>),
+    DevLogs(Vec<DevLog>),
+    ArticleTags(Vec<ArticleTag>),
+    Checksums(Vec<TableChecksum>),
+    /// `None` if the page isn't cached
+    Page(Option<Page>),
+    /// Content of each range requested by [`UtilityCommand::GetFileRanges`]
+    Ranges(Vec<Vec<u8>>),
+    FileCacheStats(FileCacheStats),
+    Template(Arc<Template>),
+    SearchIndex(Arc<SearchIndex>),
+    Metadata {
+        len: u64,
+        modified: Option<SystemTime>,
+        /// Hex digest of the file's content. `None` if it wasn't asked for & isn't known.
+        hash: Option<String>,
+    },
+}
+
+pub type UtilitySender = mpsc::Sender<(UtilityCommand, Sender<Result<UtilityData, ()>>)>;
+
+type Reply = Sender<Result<UtilityData, ()>>;
+
+struct DBJob {
+    statement: String,
+    param: Vec<String>,
+    table: Table,
+    reply: Reply,
+}
+
+struct FileJob {
+    file: PathBuf,
+    request: FileRequest,
+    reply: Reply,
+}
+
+enum FileRequest {
+    Read { bytes: bool },
+    Ranges(Vec<(u64, u64)>),
+    Metadata { digest: bool },
+    Template,
+}
+
+/// Spawns the utility thread.
+///
+/// The thread blocks until a command arrives & hands it to the DB or file worker pool.
+/// A full pool doesn't hold it up: jobs wait for room in the pool's queue on their own,
+/// failing after `queue_timeout`.
+/// It shuts down once [`UtilityCommand::Shutdown`] is received or every sender is dropped.
+/// Unless disabled, a second thread checksums the tables rendered pages are built from.
+/// A third builds the search index, rebuilding it whenever pages are invalidated.
+pub fn generate_utility_thread(setting: &UtilitySetting) -> (UtilitySender, JoinHandle<()>) {
+    // generate channel
+    let (tx, rx): (UtilitySender, _) = mpsc::channel();
+
+    let setting = setting.clone();
+
+    if setting.page_check_interval > 0 {
+        page_cache::watch_tables(tx.clone(), Duration::from_secs(setting.page_check_interval));
+    }
+
+    let refresh_search = search::watch(tx.clone());
+    let queue_timeout = Duration::from_millis(setting.queue_timeout);
+
+    // create thread
+    let thread = thread::spawn(move || {
+        let db_pool = DbPool::from_env(setting.db_pool_min, setting.db_pool_max).ok();
+
+        let db_workers = WorkerPool::new(
+            "db",
+            setting.db_workers,
+            setting.db_queue,
+            queue_timeout,
+            move |job: DBJob| {
+                let result = db_query(&db_pool, job.statement, job.param, job.table);
+                let _ = job.reply.send(result);
+            },
+            |job: DBJob| {
+                let _ = job.reply.send(Err(()));
+            },
+        );
+        let digests = DigestCache::new(setting.digest_cache_size);
+        let templates = TemplateCache::default();
+        let file_cache = Arc::new(FileCache::new(
+            setting.file_cache_size,
+            setting.file_cache_max_entry,
+        ));
+
+        let file_workers = WorkerPool::new(
+            "file",
+            setting.file_workers,
+            setting.file_queue,
+            queue_timeout,
+            {
+                let file_cache = file_cache.clone();
+
+                move |job: FileJob| {
+                    let result = match job.request {
+                        FileRequest::Read { bytes } => read_file(&job.file, bytes, &file_cache),
+                        FileRequest::Ranges(ranges) => read_ranges(&job.file, &ranges),
+                        FileRequest::Metadata { digest } => {
+                            read_metadata(&job.file, digest, &digests)
+                        }
+                        FileRequest::Template => match templates.get(&job.file) {
+                            Ok(template) => Ok(UtilityData::Template(template)),
+                            Err(err) => {
+                                error!("{err}");
+                                Err(())
+                            }
+                        },
+                    };
+
+                    let _ = job.reply.send(result);
+                }
+            },
+            |job: FileJob| {
+                let _ = job.reply.send(Err(()));
+            },
+        );
+
+        let mut pages = PageCache::new(Duration::from_secs(setting.page_ttl));
+        let mut search_index: Option<Arc<SearchIndex>> = None;
+
+        while let Ok((utility_command, sender)) = rx.recv() {
+            trace!("Cmd: {utility_command:?}");
+            match utility_command {
+                UtilityCommand::DBQuery {
+                    statement,
+                    param,
+                    table,
+                } => {
+                    let job = DBJob {
+                        statement,
+                        param,
+                        table,
+                        reply: sender,
+                    };
+
+                    if let Err(job) = db_workers.submit(job) {
+                        let _ = job.reply.send(Err(()));
+                    }
+                }
+                UtilityCommand::GetFile { file, bytes } => {
+                    let job = FileJob {
+                        file,
+                        request: FileRequest::Read { bytes },
+                        reply: sender,
+                    };
+
+                    if let Err(job) = file_workers.submit(job) {
+                        let _ = job.reply.send(Err(()));
+                    }
+                }
+                UtilityCommand::GetFileRanges { file, ranges } => {
+                    let job = FileJob {
+                        file,
+                        request: FileRequest::Ranges(ranges),
+                        reply: sender,
+                    };
+
+                    if let Err(job) = file_workers.submit(job) {
+                        let _ = job.reply.send(Err(()));
+                    }
+                }
+                UtilityCommand::GetMetadata { file, digest } => {
+                    let job = FileJob {
+                        file,
+                        request: FileRequest::Metadata { digest },
+                        reply: sender,
+                    };
+
+                    if let Err(job) = file_workers.submit(job) {
+                        let _ = job.reply.send(Err(()));
+                    }
+                }
+                UtilityCommand::GetPage { slug } => {
+                    let _ = sender.send(Ok(UtilityData::Page(pages.get(&slug))));
+                }
+                UtilityCommand::CachePage { slug, page } => {
+                    pages.insert(slug, page);
+                    let _ = sender.send(Ok(UtilityData::Page(None)));
+                }
+                UtilityCommand::InvalidatePages => {
+                    pages.clear();
+                    let _ = refresh_search.send(());
+                    let _ = sender.send(Ok(UtilityData::Page(None)));
+                }
+                UtilityCommand::GetSearchIndex => {
+                    let _ =
+                        sender.send(search_index.clone().map(UtilityData::SearchIndex).ok_or(()));
+                }
+                UtilityCommand::SetSearchIndex { index } => {
+                    search_index = Some(index.clone());
+                    let _ = sender.send(Ok(UtilityData::SearchIndex(index)));
+                }
+                UtilityCommand::GetTemplate { file } => {
+                    let job = FileJob {
+                        file,
+                        request: FileRequest::Template,
+                        reply: sender,
+                    };
+
+                    if let Err(job) = file_workers.submit(job) {
+                        let _ = job.reply.send(Err(()));
+                    }
+                }
+                UtilityCommand::FileCacheStats => {
+                    let _ = sender.send(Ok(UtilityData::FileCacheStats(file_cache.stats())));
+                }
+                UtilityCommand::Shutdown => {
+                    db_workers.shutdown();
+                    file_workers.shutdown();
+
+                    info!("File cache: {:?}", file_cache.stats());
+
+                    let _ = sender.send(Ok(UtilityData::String(Vec::new())));
+                    return;
+                }
+            }
+        }
+
+        db_workers.shutdown();
+        file_workers.shutdown();
+    });
+
+    (tx, thread)
+}
+
+fn db_query(
+    db_pool: &Option<Arc<DbPool>>,
+    statement: String,
+    params: Vec<String>,
+    table: Table,
+) -> Result<UtilityData, ()> {
+    let Some(db_pool) = db_pool else {
+        error!("No database pool");
+        return Err(());
+    };
+
+    let mut conn = db_pool.get()?;
+
+    let statement = match conn.prep(statement) {
+        Ok(statement) => statement,
+        Err(err) => {
+            error!("Failed to prep: {err}");
+            return Err(());
+        }
+    };
+
+    let rows = match conn.exec_iter(statement, params) {
+        Ok(val) => val,
+        Err(err) => {
+            error!("Failed to execute: {err:#?}");
+            return Err(());
+        }
+    };
+
+    let rows = match rows.collect::<Result<Vec<Row>, _>>() {
+        Ok(rows) => rows,
+        Err(err) => {
+            error!("Failed to parse data: {err:#?}");
+            return Err(());
+        }
+    };
+
+    match table {
+        Table::Skills => parse_rows(rows).map(UtilityData::Skills),
+        Table::Projects => parse_rows(rows).map(UtilityData::Projects),
+        Table::Tags => parse_rows(rows).map(UtilityData::Tags),
+        Table::Relations => parse_rows(rows).map(UtilityData::Relations),
+        Table::DevLogs => parse_rows(rows).map(UtilityData::DevLogs),
+        Table::ArticleTags => parse_rows(rows).map(UtilityData::ArticleTags),
+        Table::Checksums => parse_rows(rows).map(UtilityData::Checksums),
+    }
+}
+
+fn parse_rows<T: FromRow>(rows: Vec<Row>) -> Result<Vec<T>, ()> {
+    rows.into_iter()
+        .map(|row| {
+            T::from_row_opt(row).map_err(|err| {
+                error!("Failed to parse row: {:?}", err.0);
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::{error, info, trace};
+
+use crate::model::TableChecksum;
+
+use super::{Table, UtilityCommand, UtilityData, UtilitySender};
+
+/// Tables a rendered page is built from
+const CHECKSUM_TABLES: &str = "CHECKSUM TABLE tag, relate_tags, dev_log, project_details";
+
+/// Rendered page kept by the utility thread
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub content: Vec<u8>,
+    pub last_modified: Option<SystemTime>,
+}
+
+/// Rendered pages keyed by slug. Pages expire once they are older than the TTL.
+pub struct PageCache {
+    ttl: Duration,
+    pages: HashMap<String, (Instant, Page)>,
+}
+
+impl PageCache {
+    pub fn new(ttl: Duration) -> Self {
+        PageCache {
+            ttl,
+            pages: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, slug: &str) -> Option<Page> {
+        match self.pages.get(slug) {
+            Some((rendered, page)) if rendered.elapsed() < self.ttl => Some(page.clone()),
+            Some(_) => {
+                trace!("{slug} expired");
+                self.pages.remove(slug);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&mut self, slug: String, page: Page) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        self.pages.insert(slug, (Instant::now(), page));
+    }
+
+    pub fn clear(&mut self) {
+        info!("Invalidated {} cached pages", self.pages.len());
+        self.pages.clear();
+    }
+}
+
+/// Spawns a thread that checksums the page tables every `interval` &
+/// invalidates the cached pages when a row changes.
+///
+/// The thread stops once the utility thread is gone.
+pub fn watch_tables(utility_thread: UtilitySender, interval: Duration) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut previous: Option<Vec<TableChecksum>> = None;
+
+        loop {
+            thread::sleep(interval);
+
+            let (tx, rx) = mpsc::channel();
+            let sent = utility_thread.send((
+                UtilityCommand::DBQuery {
+                    statement: String::from(CHECKSUM_TABLES),
+                    param: Vec::new(),
+                    table: Table::Checksums,
+                },
+                tx,
+            ));
+
+            if sent.is_err() {
+                return;
+            }
+
+            let checksums = match rx.recv() {
+                Ok(Ok(UtilityData::Checksums(checksums))) => checksums,
+                Ok(_) => {
+                    error!("Failed to checksum page tables");
+                    continue;
+                }
+                // dropped without reply; the utility thread shut down
+                Err(_) => return,
+            };
+
+            if previous
+                .as_ref()
+                .is_some_and(|previous| *previous != checksums)
+            {
+                trace!("Page tables changed: {checksums:?}");
+
+                let (tx, _rx) = mpsc::channel();
+                if utility_thread
+                    .send((UtilityCommand::InvalidatePages, tx))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+
+            previous = Some(checksums);
+        }
+    })
+}
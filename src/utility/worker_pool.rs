@@ -0,0 +1,226 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{error, trace};
+
+/// How often a full queue is checked for room
+const QUEUE_POLL: Duration = Duration::from_millis(1);
+
+/// Fixed set of worker threads pulling jobs off a bounded queue.
+///
+/// Submitted jobs go to a feeder thread, which waits for room in the queue, so a full
+/// pool never holds up the utility thread. The queue size limits the load: jobs that
+/// can't get into the queue within the timeout are rejected, so none waits longer.
+pub struct WorkerPool<J> {
+    name: String,
+    /// Jobs & when they were submitted, drained by the feeder
+    intake: Sender<(Instant, J)>,
+    feeder: Option<JoinHandle<()>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static> WorkerPool<J> {
+    /// `queue_size` must be at least 1. `reject` gets the jobs that timed out.
+    pub fn new<F, R>(
+        name: &str,
+        workers: usize,
+        queue_size: usize,
+        queue_timeout: Duration,
+        work: F,
+        reject: R,
+    ) -> WorkerPool<J>
+    where
+        F: Fn(J) + Send + Sync + 'static,
+        R: Fn(J) + Send + 'static,
+    {
+        // an empty queue would only take jobs while a worker is already waiting
+        let (tx, rx) = mpsc::sync_channel::<J>(queue_size.max(1));
+        let rx = Arc::new(Mutex::new(rx));
+        let work = Arc::new(work);
+
+        let workers = (0..workers.max(1))
+            .filter_map(|index| {
+                let rx = rx.clone();
+                let work = work.clone();
+
+                let worker = thread::Builder::new()
+                    .name(format!("{name}-{index}"))
+                    .spawn(move || loop {
+                        // lock is released before the job runs
+                        let job = rx.lock().unwrap().recv();
+
+                        match job {
+                            Ok(job) => work(job),
+                            Err(_) => break,
+                        }
+                    });
+
+                match worker {
+                    Ok(worker) => Some(worker),
+                    Err(err) => {
+                        error!("Failed to spawn {name}-{index}: {err}");
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let (intake, intake_rx) = mpsc::channel();
+
+        let feeder = {
+            let name = String::from(name);
+
+            thread::Builder::new()
+                .name(format!("{name}-feeder"))
+                .spawn(move || feed(&name, intake_rx, tx, queue_timeout, reject))
+        };
+
+        let feeder = match feeder {
+            Ok(feeder) => Some(feeder),
+            Err(err) => {
+                error!("Failed to spawn {name}-feeder: {err}");
+                None
+            }
+        };
+
+        WorkerPool {
+            name: String::from(name),
+            intake,
+            feeder,
+            workers,
+        }
+    }
+
+    /// Hands a job to the feeder without blocking. The job is handed back if the pool
+    /// has stopped.
+    pub fn submit(&self, job: J) -> Result<(), J> {
+        self.intake.send((Instant::now(), job)).map_err(|err| {
+            error!("{} feeder has stopped", self.name);
+            err.0 .1
+        })
+    }
+
+    /// Lets the workers finish submitted jobs & waits for them to exit
+    pub fn shutdown(self) {
+        let WorkerPool {
+            name,
+            intake,
+            feeder,
+            workers,
+        } = self;
+
+        // the feeder drops the queue once it has fed the remaining jobs
+        drop(intake);
+
+        if let Some(feeder) = feeder {
+            if feeder.join().is_err() {
+                error!("{name} feeder panicked");
+            }
+        }
+
+        for worker in workers {
+            if worker.join().is_err() {
+                error!("{name} worker panicked");
+            }
+        }
+
+        trace!("{name} shut down");
+    }
+}
+
+/// Moves jobs into the queue, waiting for room until each job's timeout runs out
+fn feed<J, R: Fn(J)>(
+    name: &str,
+    intake: Receiver<(Instant, J)>,
+    queue: SyncSender<J>,
+    queue_timeout: Duration,
+    reject: R,
+) {
+    for (submitted, mut job) in intake {
+        let deadline = submitted + queue_timeout;
+
+        loop {
+            match queue.try_send(job) {
+                Ok(()) => break,
+                Err(TrySendError::Full(full)) if Instant::now() < deadline => {
+                    job = full;
+                    thread::sleep(QUEUE_POLL);
+                }
+                Err(TrySendError::Full(job)) => {
+                    error!("{name} queue is full");
+                    reject(job);
+                    break;
+                }
+                Err(TrySendError::Disconnected(job)) => {
+                    error!("{name} workers have stopped");
+                    reject(job);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_jobs() {
+        let (tx, rx) = mpsc::channel();
+        let pool = WorkerPool::new(
+            "test",
+            2,
+            4,
+            Duration::from_secs(1),
+            move |job| tx.send(job).unwrap(),
+            |_| {},
+        );
+
+        for job in 0..10 {
+            pool.submit(job).unwrap();
+        }
+        pool.shutdown();
+
+        let mut done: Vec<i32> = rx.iter().collect();
+        done.sort();
+
+        assert_eq!(done, (0..10).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn rejects_jobs_that_wait_too_long() {
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let (rejected_tx, rejected_rx) = mpsc::channel();
+
+        let pool = WorkerPool::new(
+            "test",
+            1,
+            1,
+            Duration::from_millis(200),
+            move |_: i32| {
+                let _ = release_rx.lock().unwrap().recv();
+            },
+            move |job| rejected_tx.send(job).unwrap(),
+        );
+
+        // one job is worked on, one queued & the last can't get in
+        let submitted = Instant::now();
+        for job in 0..3 {
+            pool.submit(job).unwrap();
+        }
+        assert!(submitted.elapsed() < Duration::from_millis(200));
+
+        assert_eq!(rejected_rx.recv_timeout(Duration::from_secs(2)), Ok(2));
+
+        drop(release_tx);
+        pool.shutdown();
+    }
+}